@@ -0,0 +1,128 @@
+// Symbol id allocation. Environments that share an allocator never hand out the same symbol,
+// so values can move between sibling and child scopes.
+// Each document can reserve its own range, so its ids stay stable as other documents change.
+
+use crate::constants::*;
+use crate::utils::create_pointer_symbol;
+use alloc::vec::Vec;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+
+pub type SharedAllocator = Rc<RefCell<SymbolAllocator>>;
+
+#[derive(Debug,Clone)]
+pub struct SymbolAllocator {
+    next_id: u64,
+    // Exclusive. Never past the 48 bit payload.
+    end: u64,
+    // Freed ids, reused before allocating new ones.
+    free_ids: Vec<u64>
+}
+
+impl SymbolAllocator {
+    // Allocates from start up to the end of the payload space.
+    pub fn new(start: u64) -> SymbolAllocator {
+        return SymbolAllocator::with_range(start, PAYLOAD_MASK + 1);
+    }
+
+    pub fn with_range(start: u64, end: u64) -> SymbolAllocator {
+        return SymbolAllocator {
+            next_id: start,
+            end: end.min(PAYLOAD_MASK + 1),
+            free_ids: Vec::new()
+        }
+    }
+
+    pub fn shared(start: u64) -> SharedAllocator {
+        return Rc::new(RefCell::new(SymbolAllocator::new(start)));
+    }
+
+    // A pointer symbol. RUNTIME_ERR_SYMBOL_OVERFLOW once the range is used up.
+    pub fn allocate(&mut self) -> Result<u64, u64> {
+        if let Some(id) = self.free_ids.pop() {
+            return Ok(create_pointer_symbol(id));
+        }
+        if self.next_id >= self.end {
+            return Err(RUNTIME_ERR_SYMBOL_OVERFLOW);
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        return Ok(create_pointer_symbol(id));
+    }
+
+    // Return a symbol for reuse. Symbols outside this allocator's range are ignored.
+    // The caller must ensure nothing still refers to it.
+    pub fn free(&mut self, symbol: u64) {
+        let id = symbol & PAYLOAD_MASK;
+        if id < self.next_id && id < self.end && !self.free_ids.contains(&id) {
+            self.free_ids.push(id);
+        }
+    }
+
+    // Keep only the freed symbols that match. i.e. after restoring a snapshot that still uses some.
+    pub fn retain_free<F: Fn(u64) -> bool>(&mut self, keep: F) {
+        self.free_ids.retain(|id| keep(create_pointer_symbol(*id)));
+    }
+
+    // Carve out the next count ids as a separate allocator. i.e. one per document.
+    pub fn reserve(&mut self, count: u64) -> Result<SymbolAllocator, u64> {
+        let start = self.next_id;
+        let end = match start.checked_add(count) {
+            Some(end) if end <= self.end => end,
+            _ => return Err(RUNTIME_ERR_SYMBOL_OVERFLOW)
+        };
+        self.next_id = end;
+        return Ok(SymbolAllocator::with_range(start, end));
+    }
+
+    // Ids that can still be handed out
+    pub fn remaining(&self) -> u64 {
+        return (self.end - self.next_id) + self.free_ids.len() as u64;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocate_and_reuse() {
+        let mut allocator = SymbolAllocator::new(APP_SYMBOL_START);
+        let a = allocator.allocate().unwrap();
+        let b = allocator.allocate().unwrap();
+        assert_eq!(a, APP_SYMBOL_START | VALUE_T_PTR_OBJ);
+        assert_eq!(b, a + 1);
+
+        allocator.free(a);
+        allocator.free(a);
+        // Never allocated, so it isn't reused
+        allocator.free(b + 100);
+        assert_eq!(allocator.allocate(), Ok(a));
+        assert_eq!(allocator.allocate(), Ok(b + 1));
+    }
+
+    #[test]
+    fn test_document_ranges() {
+        let mut allocator = SymbolAllocator::new(APP_SYMBOL_START);
+        let mut doc_a = allocator.reserve(2).unwrap();
+        let mut doc_b = allocator.reserve(2).unwrap();
+        assert_eq!(doc_a.allocate(), Ok(APP_SYMBOL_START | VALUE_T_PTR_OBJ));
+        assert_eq!(doc_b.allocate(), Ok((APP_SYMBOL_START + 2) | VALUE_T_PTR_OBJ));
+        assert_eq!(allocator.allocate(), Ok((APP_SYMBOL_START + 4) | VALUE_T_PTR_OBJ));
+
+        doc_a.allocate().unwrap();
+        assert_eq!(doc_a.allocate(), Err(RUNTIME_ERR_SYMBOL_OVERFLOW));
+        assert_eq!(doc_a.remaining(), 0);
+    }
+
+    #[test]
+    fn test_payload_overflow() {
+        let mut allocator = SymbolAllocator::new(PAYLOAD_MASK);
+        assert_eq!(allocator.allocate(), Ok(PAYLOAD_MASK | VALUE_T_PTR_OBJ));
+        assert_eq!(allocator.allocate(), Err(RUNTIME_ERR_SYMBOL_OVERFLOW));
+        assert_eq!(allocator.reserve(1).err(), Some(RUNTIME_ERR_SYMBOL_OVERFLOW));
+        assert_eq!(SymbolAllocator::new(APP_SYMBOL_START).reserve(u64::MAX).err(), Some(RUNTIME_ERR_SYMBOL_OVERFLOW));
+    }
+}
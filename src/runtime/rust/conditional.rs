@@ -0,0 +1,163 @@
+// Branching. if / else if / else chains and guards (cond: value).
+// Branches are closures so only the taken branch is evaluated.
+// Mirrors ConditionalExpr, ConditionalClauseExpr and GuardExpr in the JS compiler.
+
+use crate::environment::Environment;
+use crate::expression::Expression;
+use crate::constants::*;
+use crate::types::*;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+
+pub type Branch<'a> = Box<dyn FnMut(&mut Environment) -> u64 + 'a>;
+
+pub struct ConditionalClause<'a> {
+    // None for the terminal else clause
+    pub condition: Option<Branch<'a>>,
+    pub body: Branch<'a>,
+    // Symbols referenced anywhere in the condition or body
+    pub depends_on: Vec<u64>
+}
+
+impl<'a> ConditionalClause<'a> {
+    pub fn new(condition: Option<Branch<'a>>, body: Branch<'a>, depends_on: Vec<u64>) -> ConditionalClause<'a> {
+        return ConditionalClause {
+            condition: condition,
+            body: body,
+            depends_on: depends_on
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        return self.condition.is_none();
+    }
+}
+
+pub struct Conditional<'a> {
+    pub clauses: Vec<ConditionalClause<'a>>
+}
+
+impl<'a> Conditional<'a> {
+    // Starts with the if clause. Each clause's dependencies are added to the expression
+    // containing the conditional, so it recalculates whichever branch a change affects.
+    pub fn new(owner: &mut Expression, condition: Branch<'a>, body: Branch<'a>, depends_on: Vec<u64>) -> Conditional<'a> {
+        owner.add_dependencies(&depends_on);
+        return Conditional {
+            clauses: vec![ConditionalClause::new(Some(condition), body, depends_on)]
+        }
+    }
+
+    // Add an else if / else clause. Clauses can't follow the final else.
+    pub fn append(&mut self, owner: &mut Expression, clause: ConditionalClause<'a>) -> bool {
+        if let Some(last) = self.clauses.last() {
+            if last.is_terminal() {
+                return false;
+            }
+        }
+        owner.add_dependencies(&clause.depends_on);
+        self.clauses.push(clause);
+        return true;
+    }
+
+    // Dependencies of every branch, whichever one is taken.
+    // Conservative so that the cell recalculates when the condition flips.
+    pub fn depends_on(&self) -> Vec<u64> {
+        let mut symbols: Vec<u64> = Vec::new();
+        for clause in self.clauses.iter() {
+            for symbol in clause.depends_on.iter() {
+                if !symbols.contains(symbol) {
+                    symbols.push(*symbol);
+                }
+            }
+        }
+        return symbols;
+    }
+
+    // Evaluate conditions in order and return the body of the first true one.
+    // Errors in a condition are returned as is. Without a matching clause, the result is None.
+    pub fn eval(&mut self, env: &mut Environment) -> u64 {
+        for clause in self.clauses.iter_mut() {
+            if let Some(condition) = clause.condition.as_mut() {
+                let result = condition(env);
+                if is_error(result) {
+                    return result;
+                }
+                if !__av_truthy(env, result) {
+                    continue;
+                }
+            }
+            return (clause.body)(env);
+        }
+        return SYMBOL_NONE.symbol;
+    }
+}
+
+// Guard. cond: value. The value if the condition holds, otherwise None.
+pub fn __av_guard(env: &mut Environment, condition: u64, body: &mut dyn FnMut(&mut Environment) -> u64) -> u64 {
+    if is_error(condition) {
+        return condition;
+    }
+    if __av_truthy(env, condition) {
+        return body(env);
+    }
+    return SYMBOL_NONE.symbol;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operators::__av_div;
+    use core::cell::Cell;
+
+    fn num(value: f64) -> u64 {
+        return value.to_bits();
+    }
+
+    #[test]
+    fn test_if_else_chain() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let mut expr = Expression::new(1, "if x: 1 / 0 else if 2: 20 else: 30".to_string());
+        let evaluated = Cell::new(0);
+        let x = APP_SYMBOL_START | VALUE_T_PTR_OBJ;
+        let y = x + 1;
+
+        let mut cond = Conditional::new(&mut expr,
+            Box::new(|_| SYMBOL_FALSE.symbol),
+            Box::new(|env| { evaluated.set(evaluated.get() + 1); __av_div(env, num(1.0), num(0.0)) }),
+            vec![x]);
+        assert_eq!(cond.eval(&mut env), SYMBOL_NONE.symbol);
+
+        assert!(cond.append(&mut expr, ConditionalClause::new(Some(Box::new(|_| num(2.0))), Box::new(|_| num(20.0)), vec![x, y])));
+        assert!(cond.append(&mut expr, ConditionalClause::new(None, Box::new(|_| num(30.0)), vec![])));
+        assert!(!cond.append(&mut expr, ConditionalClause::new(None, Box::new(|_| num(40.0)), vec![y + 1])));
+
+        assert_eq!(cond.eval(&mut env), num(20.0));
+        // The untaken branch is never evaluated, but its dependencies are kept
+        assert_eq!(evaluated.get(), 0);
+        assert_eq!(cond.depends_on(), vec![x, y]);
+        // Including on the expression, without the rejected clause
+        assert_eq!(expr.depends_on, vec![x, y]);
+    }
+
+    #[test]
+    fn test_condition_errors() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let mut expr = Expression::new(1, "if 1 / 0: 1 else: 2".to_string());
+        let mut cond = Conditional::new(&mut expr,
+            Box::new(|env| __av_div(env, num(1.0), num(0.0))),
+            Box::new(|_| num(1.0)),
+            vec![]);
+        cond.append(&mut expr, ConditionalClause::new(None, Box::new(|_| num(2.0)), vec![]));
+        assert_eq!(cond.eval(&mut env), RUNTIME_ERR_DIV_Z);
+    }
+
+    #[test]
+    fn test_guard() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        assert_eq!(__av_guard(&mut env, SYMBOL_TRUE.symbol, &mut |_| num(5.0)), num(5.0));
+        assert_eq!(__av_guard(&mut env, SYMBOL_EMPTY_STR, &mut |_| num(5.0)), SYMBOL_NONE.symbol);
+        assert_eq!(__av_guard(&mut env, RUNTIME_ERR_DIV_Z, &mut |_| num(5.0)), RUNTIME_ERR_DIV_Z);
+    }
+}
@@ -30,6 +30,7 @@ use crate::functions::NativeFn2;
 use crate::structs::{Keyword, Module};
 use crate::operators::*;
 use crate::functions::*;
+use crate::record::__av_is;
 
 
 pub const SIGNALING_NAN: u64 = 0xFFF8_0000_0000_0000;
@@ -75,6 +76,7 @@ pub const SYMBOL_FALSE: Keyword = Keyword {
     symbol: 0xFFFB_0000_0000_001A,
     name: "False",
     precedence: None,
+    short_circuit: None,
     operation: None
 };
 
@@ -82,6 +84,7 @@ pub const SYMBOL_NONE: Keyword = Keyword {
     symbol: 0xFFFB_0000_0000_001B,
     name: "None",
     precedence: None,
+    short_circuit: None,
     operation: None
 };
 
@@ -93,10 +96,12 @@ pub const SYMBOL_EMPTY_ARR: u64         = 0xFFFB_0000_0000_0042;
 pub const SYMBOL_EMPTY_STR: u64         = 0xFFFA_0000_0000_FFFF;
 
 
-// Internal sentinel nodes for hash tables. (Unused)
+// Internal sentinel nodes for hash tables. See hashtable.rs
 pub const SYMBOL_SENTINEL_EMPTY: u64    = 0xFFFB_0000_0000_004A;
 pub const SYMBOL_SENTINEL_DELETED: u64  = 0xFFFB_0000_0000_004B;
 pub const SYMBOL_SENTINEL_SENTINEL: u64 = 0xFFFB_0000_0000_004C;
+// Internal default for record fields that must be given on construction. See record.rs
+pub const SYMBOL_REQUIRED_FIELD: u64    = 0xFFFB_0000_0000_004D;
 
 
 // Truthy value symbols
@@ -106,6 +111,7 @@ pub const SYMBOL_TRUE: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0019,
     name: "True",
     precedence: None,
+    short_circuit: None,
     operation: None
 };
 
@@ -113,9 +119,27 @@ pub const SYMBOL_CALL_FN: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_001C,
     name: "__call__",
     precedence: None,
+    short_circuit: None,
     operation: None
 };
 
+pub const SYMBOL_POW: Keyword = Keyword {
+    symbol: 0xFFFF_0000_0000_001D,
+    name: "^",
+    precedence: None,
+    short_circuit: None,
+    operation: Some(__av_pow)
+};
+
+// a ?? b. b when a is None. Same level as or, so a or b ?? c = (a or b) ?? c
+pub const SYMBOL_COALESCE: Keyword = Keyword {
+    symbol: 0xFFFF_0000_0000_001E,
+    name: "??",
+    precedence: Some(3),
+    short_circuit: Some(__av_coalesce_left),
+    operation: Some(__av_coalesce)
+};
+
 
 
 
@@ -128,6 +152,7 @@ pub const SYMBOL_COMMA: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0000,
     name: ",",
     precedence: Some(1),
+    short_circuit: None,
     operation: None
 };
 
@@ -135,6 +160,7 @@ pub const SYMBOL_EQUALS: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0001,
     name: "=",
     precedence: Some(2),
+    short_circuit: None,
     operation: None
 
 };
@@ -144,6 +170,7 @@ pub const SYMBOL_OR: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0002,
     name: "or",
     precedence: Some(3),
+    short_circuit: Some(__av_or_left),
     operation: Some(__av_or)
 };
 
@@ -151,6 +178,7 @@ pub const SYMBOL_AND: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0003,
     name: "and",
     precedence: Some(4),
+    short_circuit: Some(__av_and_left),
     operation: Some(__av_and)
 };
 
@@ -158,6 +186,7 @@ pub const SYMBOL_NOT: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0004,
     name: "not",
     precedence: Some(5),
+    short_circuit: None,
     operation: None    // Not is a unary, so handle it separately
 };
 
@@ -166,14 +195,16 @@ pub const SYMBOL_DBL_EQUALS: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0005,
     name: "==",
     precedence: Some(10),
-    operation: None
+    short_circuit: None,
+    operation: Some(__av_eq)
 };
 
 pub const SYMBOL_NOT_EQUALS: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0006,
     name: "!=",
     precedence: Some(10),
-    operation: None
+    short_circuit: None,
+    operation: Some(__av_ne)
 };
 
 
@@ -181,6 +212,7 @@ pub const SYMBOL_LT: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0007,
     name: "<",
     precedence: Some(15),
+    short_circuit: None,
     operation: Some(__av_lt)
 };
 
@@ -188,6 +220,7 @@ pub const SYMBOL_LTE: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0008,
     name: "<=",
     precedence: Some(15),
+    short_circuit: None,
     operation: Some(__av_lte)
 };
 
@@ -195,6 +228,7 @@ pub const SYMBOL_GT: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0009,
     name: ">",
     precedence: Some(15),
+    short_circuit: None,
     operation: Some(__av_gt)
 };
 
@@ -202,6 +236,7 @@ pub const SYMBOL_GTE: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_000A,
     name: ">=",
     precedence: Some(15),
+    short_circuit: None,
     operation: Some(__av_gte)
 };
 
@@ -210,6 +245,7 @@ pub const SYMBOL_PLUS: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_000B,
     name: "+",
     precedence: Some(20),
+    short_circuit: None,
     operation: Some(__av_add)
 };
 
@@ -217,13 +253,15 @@ pub const SYMBOL_MINUS: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_000C,
     name: "-",
     precedence: Some(20),
-    operation: Some(__av_sub)
+    short_circuit: None,
+    operation: Some(__av_sub)       // Unary minus is __av_neg. Handled separately, like not.
 };
 
 pub const SYMBOL_MULTIPLY: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_000D,
     name: "*",
     precedence: Some(21),
+    short_circuit: None,
     operation: Some(__av_mul)
 };
 
@@ -231,6 +269,7 @@ pub const SYMBOL_DIVIDE: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_000E,
     name: "/",
     precedence: Some(21),
+    short_circuit: None,
     operation: Some(__av_div)
 };
 
@@ -238,7 +277,8 @@ pub const SYMBOL_MODULO: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_000F,
     name: "%",
     precedence: Some(21),
-    operation: None         // TODO
+    short_circuit: None,
+    operation: Some(__av_mod)
 };
 
 
@@ -246,7 +286,8 @@ pub const SYMBOL_DOT: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0010,
     name: ".",
     precedence: Some(25),
-    operation: None         // TODO
+    short_circuit: None,
+    operation: Some(__av_dot)
 };
 
 
@@ -255,6 +296,7 @@ pub const SYMBOL_OPEN_PAREN: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0011,
     name: "(",
     precedence: Some(30), 
+    short_circuit: None,
     operation: None
 };
 
@@ -262,14 +304,17 @@ pub const SYMBOL_CLOSE_PAREN: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0012,
     name: ")",
     precedence: Some(30),
+    short_circuit: None,
     operation: None
 };
 
 
+// List literals [a, b, c] are built with __av_list_literal. list[i] is __av_index.
 pub const SYMBOL_OPEN_SQBR: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0013,
     name: "[",
     precedence: None,
+    short_circuit: None,
     operation: None
 };
 
@@ -277,6 +322,7 @@ pub const SYMBOL_CLOSE_SQBR: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0014,
     name: "]",
     precedence: None,
+    short_circuit: None,
     operation: None
 };
 
@@ -284,6 +330,7 @@ pub const SYMBOL_OPEN_BRACE: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0015,
     name: "{",
     precedence: None,
+    short_circuit: None,
     operation: None
 };
 
@@ -291,6 +338,7 @@ pub const SYMBOL_CLOSE_BRACE: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0016,
     name: "}",
     precedence: None,
+    short_circuit: None,
     operation: None
 };
 
@@ -298,6 +346,7 @@ pub const SYMBOL_COLON: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0017,
     name: ":",
     precedence: None,
+    short_circuit: None,
     operation: None
 };
 
@@ -305,6 +354,7 @@ pub const SYMBOL_SEMI_COLON: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0018,
     name: ";",
     precedence: None,
+    short_circuit: None,
     operation: None
 };
 
@@ -319,6 +369,49 @@ pub const AV_CLASS_ENVIRONMENT: u64 = 0xFFFF_0000_0000_1028;
 pub const AV_CLASS_STRING: u64 = 0xFFFF_0000_0000_1029;
 
 
+// Built in properties, accessed with the dot operator. i.e. name.length
+pub const AV_PROP_LENGTH: Keyword = Keyword {
+    symbol: 0xFFFF_0000_0000_1200,
+    name: "length",
+    precedence: None,
+    short_circuit: None,
+    operation: None
+};
+
+pub const AV_PROP_YEAR: Keyword = Keyword {
+    symbol: 0xFFFF_0000_0000_1201,
+    name: "year",
+    precedence: None,
+    short_circuit: None,
+    operation: None
+};
+
+pub const AV_PROP_MONTH: Keyword = Keyword {
+    symbol: 0xFFFF_0000_0000_1202,
+    name: "month",
+    precedence: None,
+    short_circuit: None,
+    operation: None
+};
+
+pub const AV_PROP_DAY: Keyword = Keyword {
+    symbol: 0xFFFF_0000_0000_1203,
+    name: "day",
+    precedence: None,
+    short_circuit: None,
+    operation: None
+};
+
+// Optional field deciding the truthiness of an object. See __av_truthy
+pub const AV_PROP_BOOL: Keyword = Keyword {
+    symbol: 0xFFFF_0000_0000_1204,
+    name: "bool",
+    precedence: None,
+    short_circuit: None,
+    operation: None
+};
+
+
 // Reserve up to 65k symbols for standard library usage. (Classes, functions, etc.)
 
 pub const AV_FN_MIN: Module = Module {
@@ -371,6 +464,150 @@ pub const AV_FN_SQRT: Module = Module {
     value: NativeFn1::create_atom(__av_sqrt)
 };
 
+pub const AV_FN_DECIMAL: Module = Module {
+    symbol: 0xFFFD_0000_0000_0108,
+    name: "decimal",
+    value: NativeFn1::create_atom(__av_decimal)
+};
+
+pub const AV_FN_ROUND_TO: Module = Module {
+    symbol: 0xFFFD_0000_0000_0109,
+    name: "round_to",
+    value: NativeFn2::create_atom(__av_round_to)
+};
+
+pub const AV_FN_DATE: Module = Module {
+    symbol: 0xFFFD_0000_0000_010A,
+    name: "date",
+    value: NativeFn3::create_atom(__av_date)
+};
+
+pub const AV_FN_TODAY: Module = Module {
+    symbol: 0xFFFD_0000_0000_010B,
+    name: "today",
+    value: NativeFn0::create_atom(__av_today)
+};
+
+pub const AV_FN_WEEKDAY: Module = Module {
+    symbol: 0xFFFD_0000_0000_010C,
+    name: "weekday",
+    value: NativeFn1::create_atom(__av_weekday)
+};
+
+pub const AV_FN_ADD_DAYS: Module = Module {
+    symbol: 0xFFFD_0000_0000_010D,
+    name: "add_days",
+    value: NativeFn2::create_atom(__av_add_days)
+};
+
+pub const AV_FN_DIFF: Module = Module {
+    symbol: 0xFFFD_0000_0000_010E,
+    name: "diff",
+    value: NativeFn2::create_atom(__av_diff)
+};
+
+pub const AV_FN_DAYS: Module = Module {
+    symbol: 0xFFFD_0000_0000_010F,
+    name: "days",
+    value: NativeFn1::create_atom(__av_days)
+};
+
+pub const AV_FN_LEN: Module = Module {
+    symbol: 0xFFFD_0000_0000_0110,
+    name: "len",
+    value: NativeFn1::create_atom(__av_len)
+};
+
+pub const AV_FN_SLICE: Module = Module {
+    symbol: 0xFFFD_0000_0000_0111,
+    name: "slice",
+    value: NativeFn3::create_atom(__av_slice)
+};
+
+pub const AV_FN_IS: Module = Module {
+    symbol: 0xFFFD_0000_0000_0112,
+    name: "is",
+    value: NativeFn2::create_atom(__av_is)
+};
+
+pub const AV_FN_TYPE: Module = Module {
+    symbol: 0xFFFD_0000_0000_0113,
+    name: "type",
+    value: NativeFn1::create_atom(__av_type_name)
+};
+
+pub const AV_FN_IS_NUMBER: Module = Module {
+    symbol: 0xFFFD_0000_0000_0114,
+    name: "is_number",
+    value: NativeFn1::create_atom(__av_is_number)
+};
+
+pub const AV_FN_IS_TEXT: Module = Module {
+    symbol: 0xFFFD_0000_0000_0115,
+    name: "is_text",
+    value: NativeFn1::create_atom(__av_is_text)
+};
+
+pub const AV_FN_IS_ERROR: Module = Module {
+    symbol: 0xFFFD_0000_0000_0116,
+    name: "is_error",
+    value: NativeFn1::create_atom(__av_is_error)
+};
+
+pub const AV_FN_COMPARE: Module = Module {
+    symbol: 0xFFFD_0000_0000_0117,
+    name: "compare",
+    value: NativeFn2::create_atom(__av_compare)
+};
+
+pub const AV_FN_COMPARE_IGNORE_CASE: Module = Module {
+    symbol: 0xFFFD_0000_0000_0118,
+    name: "compare_ignore_case",
+    value: NativeFn2::create_atom(__av_compare_ignore_case)
+};
+
+pub const AV_FN_SORT: Module = Module {
+    symbol: 0xFFFD_0000_0000_0119,
+    name: "sort",
+    value: NativeFn1::create_atom(__av_sort)
+};
+
+pub const AV_FN_IFERROR: Module = Module {
+    symbol: 0xFFFD_0000_0000_011A,
+    name: "iferror",
+    value: NativeFn2::create_atom(__av_iferror)
+};
+
+pub const AV_FN_ISERROR: Module = Module {
+    symbol: 0xFFFD_0000_0000_011B,
+    name: "iserror",
+    value: NativeFn1::create_atom(__av_is_error)
+};
+
+pub const AV_FN_DIV: Module = Module {
+    symbol: 0xFFFD_0000_0000_011C,
+    name: "div",
+    value: NativeFn4::create_atom(__av_div_round)
+};
+
+pub const AV_FN_MAP: Module = Module {
+    symbol: 0xFFFD_0000_0000_011D,
+    name: "map",
+    value: NativeFn2::create_atom(__av_map)
+};
+
+pub const AV_FN_FILTER: Module = Module {
+    symbol: 0xFFFD_0000_0000_011E,
+    name: "filter",
+    value: NativeFn2::create_atom(__av_filter)
+};
+
+pub const AV_FN_CATCH: Module = Module {
+    symbol: 0xFFFD_0000_0000_011F,
+    name: "catch",
+    value: NativeFn3::create_atom(__av_catch)
+};
+
 
 pub const AV_HTTP_REQUEST: u64 = 0xFFFC_0000_0000_1100;
 pub const AV_HTTP_PATH: u64 = 0xFFFC_0000_0000_1101;
@@ -388,6 +625,13 @@ pub const VALUE_ERR: u64 = 0xFFF9_0000_0000_0000;
 pub const PARSE_ERR: u64                    = 0xFFF9_0100_0000_0000;
 pub const INTERPRETER_ERR: u64              = 0xFFF9_0010_0000_0000;
 pub const RUNTIME_ERR: u64                  = 0xFFF9_0001_0000_0000;
+// Runtime codes past 0x000F. The top nibble keeps them clear of the parse and interpreter bits.
+pub const RUNTIME_ERR_EXT: u64              = 0xFFF9_1000_0000_0000;
+
+// The code identifies the error. The origin is the payload of the first cell that failed,
+// attached when the error is saved as a result. See bind_result.
+pub const ERR_CODE_MASK: u64                = 0xFFFF_FFFF_0000_0000;
+pub const ERR_ORIGIN_MASK: u64              = 0x0000_0000_FFFF_FFFF;
 
 // Parsing errors
 pub const PARSE_ERR_UNTERM_STR: u64         = 0xFFF9_0200_0000_0000;
@@ -397,6 +641,7 @@ pub const PARSE_ERR_UNEXPECTED_TOKEN: u64   = 0xFFF9_0500_0000_0000;
 pub const PARSE_ERR_UNMATCHED_PARENS: u64   = 0xFFF9_0600_0000_0000;
 pub const PARSE_ERR_USED_NAME: u64          = 0xFFF9_0700_0000_0000;
 pub const PARSE_ERR_UNK_SYMBOL: u64         = 0xFFF9_0800_0000_0000;
+pub const PARSE_ERR_INVALID_NAME: u64       = 0xFFF9_0900_0000_0000;
 
 // Type checking errors
 pub const RUNTIME_ERR_INVALID_TYPE: u64     = 0xFFF9_0001_0000_0000;
@@ -414,6 +659,23 @@ pub const RUNTIME_ERR_EXPECTED_STR: u64     = 0xFFF9_0008_0000_0000;
 
 // Arithmetic errors - 0x00
 pub const RUNTIME_ERR_DIV_Z: u64            = 0xFFF9_0009_0000_0000;
+// Result too large to represent exactly as a decimal
+pub const RUNTIME_ERR_NUM_OVERFLOW: u64     = 0xFFF9_000D_0000_0000;
+
+// Date errors
+pub const RUNTIME_ERR_INVALID_DATE: u64     = 0xFFF9_000E_0000_0000;
+
+// List errors
+pub const RUNTIME_ERR_INDEX_BOUNDS: u64     = 0xFFF9_000F_0000_0000;
+pub const RUNTIME_ERR_EXPECTED_LIST: u64    = 0xFFF9_1001_0000_0000;
+
+// Field access errors
+pub const RUNTIME_ERR_UNK_FIELD: u64        = 0xFFF9_1002_0000_0000;
+pub const RUNTIME_ERR_MISSING_FIELD: u64    = 0xFFF9_1003_0000_0000;
+pub const RUNTIME_ERR_EXPECTED_CLASS: u64   = 0xFFF9_1004_0000_0000;
+
+// No symbol ids left in the allocator's range
+pub const RUNTIME_ERR_SYMBOL_OVERFLOW: u64  = 0xFFF9_1005_0000_0000;
 
 // Function errors
 pub const RUNTIME_ERR_FN_UNK: u64     = 0xFFF9_000A_0000_0000;
@@ -0,0 +1,400 @@
+use core::cmp::Ordering;
+use core::fmt;
+use core::str::FromStr;
+use alloc::string::String;
+
+
+// Fractional digits kept by `/` when no explicit precision is given.
+pub const DECIMAL_DIV_PLACES: u32 = 16;
+pub const DECIMAL_DIV_ROUNDING: RoundingMode = RoundingMode::HalfEven;
+// Upper bound on fractional digits. Products past this are rounded back down.
+pub const DECIMAL_MAX_SCALE: u32 = 28;
+
+
+#[derive(Debug,PartialEq,Clone,Copy)]
+pub enum RoundingMode {
+    HalfEven,       // Banker's rounding. Default for money calculations.
+    HalfUp,         // Ties away from zero. "School" rounding.
+    Down,           // Towards zero (truncate)
+    Up,             // Away from zero
+    Floor,          // Towards negative infinity
+    Ceiling         // Towards positive infinity
+}
+
+impl RoundingMode {
+    // Names used by the div builtin. i.e. div(1, 3, 2, "half_up")
+    pub fn from_name(name: &str) -> Option<RoundingMode> {
+        match name.trim().to_lowercase().as_str() {
+            "half_even" => Some(RoundingMode::HalfEven),
+            "half_up" => Some(RoundingMode::HalfUp),
+            "down" => Some(RoundingMode::Down),
+            "up" => Some(RoundingMode::Up),
+            "floor" => Some(RoundingMode::Floor),
+            "ceiling" => Some(RoundingMode::Ceiling),
+            _ => None
+        }
+    }
+}
+
+
+// Exact base-10 fixed point number. value = coefficient * 10^-scale
+// i.e. Decimal { coefficient: 1250, scale: 2 } = 12.50
+// The scale is preserved through addition, so "12.50" + "1" prints as "13.50".
+#[derive(Clone,Copy)]
+pub struct Decimal {
+    pub coefficient: i128,
+    pub scale: u32
+}
+
+#[inline(always)]
+fn pow10(exp: u32) -> Option<i128> {
+    return 10i128.checked_pow(exp);
+}
+
+// Integer division of n / d, with the remainder rounded according to the mode.
+fn div_round(n: i128, d: i128, mode: RoundingMode) -> Option<i128> {
+    let quotient = n.checked_div(d)?;
+    let remainder = n % d;
+    if remainder == 0 {
+        return Some(quotient);
+    }
+
+    let negative = (n < 0) != (d < 0);
+    // Compare 2*|r| against |d| without overflowing
+    let half_cmp = remainder.unsigned_abs().cmp(&(d.unsigned_abs() - remainder.unsigned_abs()));
+
+    let away_from_zero = match mode {
+        RoundingMode::Down => false,
+        RoundingMode::Up => true,
+        RoundingMode::Floor => negative,
+        RoundingMode::Ceiling => !negative,
+        RoundingMode::HalfUp => half_cmp != Ordering::Less,
+        RoundingMode::HalfEven => match half_cmp {
+            Ordering::Greater => true,
+            Ordering::Less => false,
+            Ordering::Equal => quotient % 2 != 0
+        }
+    };
+
+    if away_from_zero {
+        if negative {
+            return quotient.checked_sub(1);
+        } else {
+            return quotient.checked_add(1);
+        }
+    }
+    return Some(quotient);
+}
+
+
+impl Decimal {
+    pub const fn new(coefficient: i128, scale: u32) -> Decimal {
+        return Decimal {
+            coefficient: coefficient,
+            scale: scale
+        }
+    }
+
+    pub fn from_int(value: i64) -> Decimal {
+        return Decimal::new(value as i128, 0);
+    }
+
+    // Convert using the shortest representation that round-trips to the same float.
+    // So 0.1 becomes exactly 0.1 rather than 0.1000000000000000055511151231257827.
+    pub fn from_f64(value: f64) -> Option<Decimal> {
+        if !value.is_finite() {
+            return None;
+        }
+        return Decimal::parse(&format!("{}", value));
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        // Go through the string form to get correctly rounded results.
+        return self.to_string().parse::<f64>().unwrap_or(f64::NAN);
+    }
+
+    // Parses plain decimal notation. "12", "-0.50", "+3.", ".25"
+    pub fn parse(input: &str) -> Option<Decimal> {
+        let text = input.trim();
+        let (negative, digits) = match text.as_bytes().first() {
+            Some(b'-') => (true, &text[1..]),
+            Some(b'+') => (false, &text[1..]),
+            _ => (false, text)
+        };
+
+        let mut coefficient: i128 = 0;
+        let mut scale: u32 = 0;
+        let mut seen_point = false;
+        let mut seen_digit = false;
+
+        for c in digits.chars() {
+            if c == '.' {
+                if seen_point {
+                    return None;
+                }
+                seen_point = true;
+            } else if c.is_ascii_digit() {
+                let digit = (c as u8 - b'0') as i128;
+                coefficient = coefficient.checked_mul(10)?.checked_add(digit)?;
+                seen_digit = true;
+                if seen_point {
+                    scale += 1;
+                }
+            } else {
+                return None;
+            }
+        }
+
+        if !seen_digit || scale > DECIMAL_MAX_SCALE {
+            return None;
+        }
+
+        if negative {
+            coefficient = -coefficient;
+        }
+        return Some(Decimal::new(coefficient, scale));
+    }
+
+    pub fn is_zero(&self) -> bool {
+        return self.coefficient == 0;
+    }
+
+    pub fn is_negative(&self) -> bool {
+        return self.coefficient < 0;
+    }
+
+    // Change the number of fractional digits, rounding if digits are dropped.
+    pub fn rescale(&self, places: u32, mode: RoundingMode) -> Option<Decimal> {
+        if places >= self.scale {
+            let factor = pow10(places - self.scale)?;
+            return Some(Decimal::new(self.coefficient.checked_mul(factor)?, places));
+        }
+        let factor = pow10(self.scale - places)?;
+        return Some(Decimal::new(div_round(self.coefficient, factor, mode)?, places));
+    }
+
+    // Bring both values to the same scale so the coefficients can be compared directly.
+    fn align(&self, other: &Decimal) -> Option<(i128, i128, u32)> {
+        let scale = self.scale.max(other.scale);
+        let a = self.rescale(scale, RoundingMode::Down)?;
+        let b = other.rescale(scale, RoundingMode::Down)?;
+        return Some((a.coefficient, b.coefficient, scale));
+    }
+
+    pub fn checked_add(&self, other: &Decimal) -> Option<Decimal> {
+        let (a, b, scale) = self.align(other)?;
+        return Some(Decimal::new(a.checked_add(b)?, scale));
+    }
+
+    pub fn checked_sub(&self, other: &Decimal) -> Option<Decimal> {
+        let (a, b, scale) = self.align(other)?;
+        return Some(Decimal::new(a.checked_sub(b)?, scale));
+    }
+
+    pub fn checked_mul(&self, other: &Decimal) -> Option<Decimal> {
+        let product = Decimal::new(
+            self.coefficient.checked_mul(other.coefficient)?,
+            self.scale + other.scale
+        );
+        if product.scale > DECIMAL_MAX_SCALE {
+            return product.rescale(DECIMAL_MAX_SCALE, RoundingMode::HalfEven);
+        }
+        return Some(product);
+    }
+
+    // Division is the only inexact operation, so the caller picks the precision and rounding.
+    // Returns None on division by zero or overflow.
+    pub fn checked_div(&self, other: &Decimal, places: u32, mode: RoundingMode) -> Option<Decimal> {
+        if other.is_zero() {
+            return None;
+        }
+        // (a * 10^-sa) / (b * 10^-sb) = q * 10^-places
+        // q = a * 10^(places - sa + sb) / b
+        let exp: i64 = places as i64 - self.scale as i64 + other.scale as i64;
+        let (numerator, denominator) = if exp >= 0 {
+            (self.coefficient.checked_mul(pow10(exp as u32)?)?, other.coefficient)
+        } else {
+            (self.coefficient, other.coefficient.checked_mul(pow10((-exp) as u32)?)?)
+        };
+        return Some(Decimal::new(div_round(numerator, denominator, mode)?, places));
+    }
+
+    // Floored modulo. The result has the same sign as the divisor, like __av_mod.
+    pub fn checked_mod(&self, other: &Decimal) -> Option<Decimal> {
+        let quotient = self.checked_div(other, 0, RoundingMode::Floor)?;
+        return self.checked_sub(&other.checked_mul(&quotient)?);
+    }
+
+    // Integer powers are exact (up to DECIMAL_MAX_SCALE). Negative powers divide.
+    pub fn checked_powi(&self, exp: i64) -> Option<Decimal> {
+        let mut result = Decimal::from_int(1);
+        let mut base = *self;
+        let mut remaining = exp.unsigned_abs();
+        while remaining > 0 {
+            if remaining & 1 == 1 {
+                result = result.checked_mul(&base)?;
+            }
+            remaining >>= 1;
+            if remaining > 0 {
+                base = base.checked_mul(&base)?;
+            }
+        }
+        if exp < 0 {
+            return Decimal::from_int(1).checked_div(&result, DECIMAL_DIV_PLACES, DECIMAL_DIV_ROUNDING);
+        }
+        return Some(result);
+    }
+
+    pub fn neg(&self) -> Decimal {
+        return Decimal::new(-self.coefficient, self.scale);
+    }
+
+    // Drop trailing fractional zeroes, keeping at least min_scale digits. 0.2500 -> 0.25
+    pub fn trim_zeros(&self, min_scale: u32) -> Decimal {
+        let mut result = *self;
+        while result.scale > min_scale && result.coefficient % 10 == 0 {
+            result.coefficient /= 10;
+            result.scale -= 1;
+        }
+        return result;
+    }
+
+    pub fn abs(&self) -> Decimal {
+        return Decimal::new(self.coefficient.abs(), self.scale);
+    }
+
+    pub fn round(&self, places: u32, mode: RoundingMode) -> Option<Decimal> {
+        if places >= self.scale {
+            // Already exact at this precision. Don't pad with extra zeroes.
+            return Some(*self);
+        }
+        return self.rescale(places, mode);
+    }
+}
+
+
+impl PartialEq for Decimal {
+    // Numeric equality. 1.5 == 1.50
+    fn eq(&self, other: &Self) -> bool {
+        return self.cmp(other) == Ordering::Equal;
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if let Some((a, b, _)) = self.align(other) {
+            return a.cmp(&b);
+        }
+        // Out of range for an exact comparison. Magnitudes are far enough apart for floats.
+        return self.to_f64().partial_cmp(&other.to_f64()).unwrap_or(Ordering::Equal);
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return Decimal::parse(s).ok_or(());
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.coefficient.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        let mut output = String::with_capacity(digits.len() + scale + 2);
+        if self.coefficient < 0 {
+            output.push('-');
+        }
+        if scale == 0 {
+            output.push_str(&digits);
+        } else if digits.len() > scale {
+            let (int_part, frac_part) = digits.split_at(digits.len() - scale);
+            output.push_str(int_part);
+            output.push('.');
+            output.push_str(frac_part);
+        } else {
+            output.push_str("0.");
+            for _ in digits.len()..scale {
+                output.push('0');
+            }
+            output.push_str(&digits);
+        }
+        write!(f, "{}", output)
+    }
+}
+
+impl fmt::Debug for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(s: &str) -> Decimal {
+        return Decimal::parse(s).unwrap();
+    }
+
+    #[test]
+    fn test_parse_display() {
+        assert_eq!(dec("12.50").to_string(), "12.50");
+        assert_eq!(dec("-0.05").to_string(), "-0.05");
+        assert_eq!(dec(".25").to_string(), "0.25");
+        assert_eq!(dec("7").to_string(), "7");
+        assert!(Decimal::parse("1.2.3").is_none());
+        assert!(Decimal::parse("abc").is_none());
+        assert!(Decimal::parse("-").is_none());
+    }
+
+    #[test]
+    fn test_exact_arithmetic() {
+        assert_eq!(dec("0.1").checked_add(&dec("0.2")).unwrap(), dec("0.3"));
+        assert_eq!(dec("0.3").checked_sub(&dec("0.1")).unwrap().to_string(), "0.2");
+        assert_eq!(dec("1.10").checked_mul(&dec("3")).unwrap().to_string(), "3.30");
+        assert_eq!(Decimal::from_f64(0.1).unwrap().to_string(), "0.1");
+    }
+
+    #[test]
+    fn test_division_rounding() {
+        let one = dec("1");
+        let three = dec("3");
+        assert_eq!(one.checked_div(&three, 2, RoundingMode::HalfEven).unwrap().to_string(), "0.33");
+        assert_eq!(dec("2").checked_div(&three, 2, RoundingMode::Down).unwrap().to_string(), "0.66");
+        assert_eq!(dec("2").checked_div(&three, 2, RoundingMode::HalfUp).unwrap().to_string(), "0.67");
+        assert!(one.checked_div(&dec("0.00"), 2, RoundingMode::HalfEven).is_none());
+    }
+
+    #[test]
+    fn test_mod_pow() {
+        assert_eq!(dec("7.5").checked_mod(&dec("2")).unwrap(), dec("1.5"));
+        assert_eq!(dec("-7.5").checked_mod(&dec("2")).unwrap(), dec("0.5"));
+        assert_eq!(dec("1.1").checked_powi(2).unwrap().to_string(), "1.21");
+        assert_eq!(dec("2").checked_powi(-2).unwrap(), dec("0.25"));
+        assert_eq!(dec("5").checked_powi(0).unwrap(), dec("1"));
+    }
+
+    #[test]
+    fn test_round_modes() {
+        assert_eq!(dec("2.345").round(2, RoundingMode::HalfEven).unwrap().to_string(), "2.34");
+        assert_eq!(dec("2.355").round(2, RoundingMode::HalfEven).unwrap().to_string(), "2.36");
+        assert_eq!(dec("2.345").round(2, RoundingMode::HalfUp).unwrap().to_string(), "2.35");
+        assert_eq!(dec("-2.345").round(2, RoundingMode::HalfUp).unwrap().to_string(), "-2.35");
+        assert_eq!(dec("-2.341").round(2, RoundingMode::Floor).unwrap().to_string(), "-2.35");
+        assert_eq!(dec("2.341").round(2, RoundingMode::Ceiling).unwrap().to_string(), "2.35");
+        assert_eq!(dec("2.5").round(5, RoundingMode::HalfEven).unwrap().to_string(), "2.5");
+        assert_eq!(RoundingMode::from_name("Half_Up"), Some(RoundingMode::HalfUp));
+        assert_eq!(RoundingMode::from_name("nearest"), None);
+    }
+}
@@ -1,15 +1,23 @@
 use crate::types::is_symbol;
-use crate::utils::create_pointer_symbol;
-use crate::structs::{Identifier, Atom};
+use crate::structs::{Identifier, Atom, NameError, ResolveError, CircularReference, UnknownName};
+use crate::suggest::rank_suggestions;
 use crate::expression::Expression;
-use crate::types::{is_pointer, is_nan};
+use crate::types::{is_pointer, is_nan, is_float_nan, is_error, with_error_origin};
 use crate::constants::*;
 use crate::macros::*;
+use crate::temporal::{Clock, system_clock};
+use crate::allocator::{SymbolAllocator, SharedAllocator};
+use crate::pmap::PersistentMap;
+#[cfg(not(target_os = "unknown"))]
+use crate::runtime::{PROPERTY_ID_MAP, SYMBOL_ID_MAP, BUILTIN_MODULES};
 
 use core::fmt;
-use fnv::FnvHashMap;
+use fnv::{FnvHashMap, FnvHashSet};
 use std::rc::Rc;
-use std::cell::RefCell;
+
+
+// Shown in unknown name errors
+const MAX_SUGGESTIONS: usize = 3;
 
 
 
@@ -17,148 +25,548 @@ use std::cell::RefCell;
 // Primary AST linked list structure
 // Pure functions are scoped to their parameters. i.e. null parent.
 // You can reference parent, but never child or sibiling data.
+// Names and values defined in a child shadow the parent's, without modifying the parent.
 #[repr(C)]
 pub struct Environment {
-    parent: Box<Option<Environment>>,
+    parent: Option<Rc<Environment>>,
     
     // Normalized upper case name -> Symbol ID for things defined in this scope
     // names
-    normname_symbols: FnvHashMap<String, u64>,
+    normname_symbols: Rc<FnvHashMap<String, u64>>,
 
     // Symbol ID -> metadata and values
     // values
-    identifiers: FnvHashMap<u64, Identifier>,
+    identifiers: PersistentMap<Identifier>,
 
-    // Raw code
-    pub body: Vec<Expression>,
+    // Normalized upper case field name -> Symbol ID. Fields are looked up by symbol
+    // on any object, so they're interned separately from the names in scope.
+    field_symbols: Rc<FnvHashMap<String, u64>>,
 
-    // TODO: Allocation when there's multiple sub-environments.
-    pub next_symbol_id: u64,
+    // Raw code. Shared with snapshots. Use Rc::make_mut to edit an expression.
+    pub body: Vec<Rc<Expression>>,
+
+    // Shared with child and sibling environments, so symbols never collide.
+    allocator: SharedAllocator,
+
+    // Time source for today(). Replace for deterministic recalculation.
+    pub clock: Clock,
 }
 
 impl Environment {
-    // TODO: Variant for creating a child environment with a parent arg
+    // With the builtin functions bound by name. See load_prelude
     pub fn new(next_symbol_id: u64) -> Environment {
+        return Environment::with_allocator(SymbolAllocator::shared(next_symbol_id));
+    }
+
+    // Without any builtins. Every name is available for user definitions.
+    pub fn new_empty(next_symbol_id: u64) -> Environment {
+        return Environment::empty(SymbolAllocator::shared(next_symbol_id));
+    }
+
+    // i.e. with a range reserved for a document
+    pub fn with_allocator(allocator: SharedAllocator) -> Environment {
+        let mut env = Environment::empty(allocator);
+        env.load_prelude();
+        return env;
+    }
+
+    fn empty(allocator: SharedAllocator) -> Environment {
         return Environment {
-            parent: Box::new(None),
-            normname_symbols: FnvHashMap::default(),
-            identifiers: FnvHashMap::default(),
+            parent: None,
+            normname_symbols: Rc::new(FnvHashMap::default()),
+            identifiers: PersistentMap::new(),
+            field_symbols: Rc::new(FnvHashMap::default()),
             body: Vec::with_capacity(0),
-            next_symbol_id: next_symbol_id,
+            allocator: allocator,
+            clock: system_clock,
         }
     }
 
-    pub fn define_identifier(&mut self) -> u64 {
-        let next_symbol: u64 = create_pointer_symbol(self.next_symbol_id);
-        self.next_symbol_id += 1;
-        return next_symbol;
+    // A nested scope which can read everything visible in the parent.
+    // Symbols come from the parent's allocator, so they never collide with the parent's symbols.
+    pub fn new_child(parent: Rc<Environment>) -> Environment {
+        // Builtins are visible through the parent
+        let mut child = Environment::empty(Rc::clone(&parent.allocator));
+        child.clock = parent.clock;
+        child.parent = Some(parent);
+        return child;
+    }
+
+    // Scope for a pure function. Nothing from the caller is visible, apart from builtins and
+    // interned field names, so the result only depends on the parameters bound into it.
+    pub fn new_isolated(&self) -> Environment {
+        let mut scope = Environment::with_allocator(Rc::clone(&self.allocator));
+        scope.clock = self.clock;
+        let mut current = Some(self);
+        while let Some(env) = current {
+            for (name, symbol) in env.field_symbols.iter() {
+                Rc::make_mut(&mut scope.field_symbols).entry(name.clone()).or_insert(*symbol);
+            }
+            current = env.parent();
+        }
+        return scope;
+    }
+
+    // Bind every builtin module's name to its function. Builtins use reserved symbols,
+    // so this doesn't take any ids from the allocator.
+    pub fn load_prelude(&mut self) {
+        #[cfg(not(target_os = "unknown"))]
+        {
+            for module in BUILTIN_MODULES.iter() {
+                // Can't fail. Each builtin has its own name.
+                let _ = self.bind_name(module.symbol, module.name.to_string());
+                self.bind_value(module.symbol, module.value.clone());
+            }
+        }
+    }
+
+    pub fn parent(&self) -> Option<&Environment> {
+        return self.parent.as_deref();
+    }
+
+    // Local copy of an identifier, so changes in this scope don't affect the parent.
+    fn local_identifier(&mut self, symbol: u64) -> &mut Identifier {
+        if !self.identifiers.contains_key(&symbol) {
+            let inherited = match self.parent().and_then(|parent| parent.lookup(symbol)) {
+                Some(ident) => ident.clone(),
+                None => Identifier {
+                    symbol: symbol,
+                    name: None,
+                    value: None
+                }
+            };
+            self.identifiers.insert(symbol, inherited);
+        }
+        return self.identifiers.get_mut(&symbol).unwrap();
+    }
+
+    // A new pointer symbol. RUNTIME_ERR_SYMBOL_OVERFLOW when the allocator's range is used up.
+    pub fn define_identifier(&mut self) -> Result<u64, u64> {
+        return self.allocator.borrow_mut().allocate();
+    }
+
+    // Remove an identifier defined in this scope and allow its symbol to be reused.
+    pub fn free_identifier(&mut self, symbol: u64) {
+        self.remove_name_mapping(symbol);
+        if self.identifiers.remove(&symbol).is_some() {
+            self.allocator.borrow_mut().free(symbol);
+        }
     }
 
     // Bind a name to an identifier within this scope.
-    pub fn bind_name(&mut self, symbol: u64, name: String) {
-        // TODO: Handling existing names
+    // PARSE_ERR_USED_NAME if another symbol in this scope already has the name. Builtins are
+    // never shadowed, even from a child scope. Names in parent scopes can be shadowed.
+    pub fn bind_name(&mut self, symbol: u64, name: String) -> Result<(), u64> {
+        validate_name(&name).map_err(|err| err.error_code())?;
         let uname = name.to_uppercase();
-        // TODO: name validation (without duplicating)
+        if self.is_builtin_name(&name) && self.lookup_by_name(name.clone()) != Some(&symbol) {
+            return Err(PARSE_ERR_USED_NAME);
+        }
+        match self.normname_symbols.get(&uname) {
+            Some(existing) if *existing != symbol => return Err(PARSE_ERR_USED_NAME),
+            Some(_) => {},
+            None => {
+                // Drop the symbol's previous name, so each symbol has one name in scope
+                self.remove_name_mapping(symbol);
+                Rc::make_mut(&mut self.normname_symbols).insert(uname, symbol);
+            }
+        }
 
-        if !self.normname_symbols.contains_key(&uname) {
-            self.normname_symbols.insert(uname, symbol);
+        self.local_identifier(symbol).name = Some(name);
+        return Ok(());
+    }
+
+    fn remove_name_mapping(&mut self, symbol: u64) {
+        if let Some(Some(old_name)) = self.identifiers.get(&symbol).map(|ident| ident.name.as_ref()) {
+            let old_uname = old_name.to_uppercase();
+            if self.normname_symbols.get(&old_uname) == Some(&symbol) {
+                Rc::make_mut(&mut self.normname_symbols).remove(&old_uname);
+            }
         }
+    }
 
-        if let Some(existing) = self.identifiers.get_mut(&symbol) {
-            existing.name = Some(name);
-        } else {
-            let variable = Identifier {
-                symbol: symbol,
-                name: Some(name),
-                value: None
-            };
-            self.identifiers.insert(symbol, variable);
+    // Symbols of the expressions that reference this symbol.
+    pub fn dependents(&self, symbol: u64) -> Vec<u64> {
+        return self.body.iter()
+            .filter(|expr| expr.depends_on.contains(&symbol))
+            .map(|expr| expr.symbol)
+            .collect();
+    }
+
+    // Give a symbol a new name within this scope. Returns the expressions that
+    // referenced the old name, so their source can be updated.
+    pub fn rename(&mut self, symbol: u64, new_name: String) -> Result<Vec<u64>, u64> {
+        if !self.identifiers.contains_key(&symbol) {
+            return Err(PARSE_ERR_UNK_SYMBOL);
+        }
+        validate_name(&new_name).map_err(|err| err.error_code())?;
+        if self.is_builtin_name(new_name.trim()) {
+            return Err(PARSE_ERR_USED_NAME);
+        }
+        let new_uname = new_name.trim().to_uppercase();
+        match self.normname_symbols.get(&new_uname) {
+            // Changing the case of a name is allowed
+            Some(existing) if *existing != symbol => return Err(PARSE_ERR_USED_NAME),
+            _ => {}
+        }
+
+        self.remove_name_mapping(symbol);
+        Rc::make_mut(&mut self.normname_symbols).insert(new_uname, symbol);
+        self.local_identifier(symbol).name = Some(new_name.trim().to_string());
+        return Ok(self.dependents(symbol));
+    }
+
+    // Remove a symbol's name from this scope. The value is kept, so existing
+    // references by symbol continue to work. Returns the expressions that referenced it.
+    pub fn unbind_name(&mut self, symbol: u64) -> Result<Vec<u64>, u64> {
+        let has_name = match self.identifiers.get(&symbol) {
+            Some(ident) => ident.name.is_some(),
+            None => false
+        };
+        if !has_name {
+            return Err(PARSE_ERR_UNK_SYMBOL);
+        }
+        self.remove_name_mapping(symbol);
+        self.local_identifier(symbol).name = None;
+        return Ok(self.dependents(symbol));
+    }
+
+    // Symbol for a field name, as used after the dot operator. i.e. customer.name
+    // Built in property names map to their reserved symbols.
+    pub fn field_symbol(&mut self, name: &str) -> Result<u64, u64> {
+        let norm_name = name.trim().to_uppercase();
+        #[cfg(not(target_os = "unknown"))]
+        {
+            if let Some(property) = PROPERTY_ID_MAP.get(&norm_name) {
+                return Ok(property.symbol);
+            }
+        }
+        let mut current = Some(&*self);
+        while let Some(env) = current {
+            if let Some(symbol) = env.field_symbols.get(&norm_name) {
+                return Ok(*symbol);
+            }
+            current = env.parent();
         }
+        let symbol = self.define_identifier()?;
+        self.identifiers.insert(symbol, Identifier {
+            symbol: symbol,
+            name: Some(name.trim().to_string()),
+            value: None
+        });
+        Rc::make_mut(&mut self.field_symbols).insert(norm_name, symbol);
+        return Ok(symbol);
     }
 
     // Bind an identifier to a value
     pub fn bind_value(&mut self, symbol: u64, value: Atom) {
-        // self.identifiers.insert(symbol, value)
-        if let Some(existing) = self.identifiers.get_mut(&symbol) {
-            existing.value = Some(value);
-        } else {
-            let variable = Identifier {
-                symbol: symbol,
-                name: None,
-                value: Some(value)
-            };
-            self.identifiers.insert(symbol, variable);
-        }
+        self.local_identifier(symbol).value = Some(value);
     }
 
     // A lower-level form of bind_value to save the stack result
+    // Errors are tagged with this symbol, unless they came from an earlier cell.
     pub fn bind_result(&mut self, symbol: u64, result: u64) {
+        let result = with_error_origin(result, symbol);
         let atom = resolve_atom!(&self, result);
         self.bind_value(symbol, atom);
     }
 
+    // A new identifier bound to the value. The overflow error is returned as the value,
+    // like any other runtime error.
     pub fn init_value(&mut self, value: Atom) -> u64 {
-        let symbol_id = self.define_identifier();
+        let symbol_id = match self.define_identifier() {
+            Ok(symbol) => symbol,
+            Err(err) => return err
+        };
         self.bind_value(symbol_id, value);
         return symbol_id;
     }
 
-    // Check whether a name has already been used within this scope
-    // Note that it doesn't check whether it's used outside of it.
+    // Check whether a name can be defined within this scope.
+    // Note that it doesn't check whether it's used outside of it, so names may shadow the parent's.
+    pub fn check_name(&self, name: &str) -> Result<(), NameError> {
+        validate_name(name)?;
+        if self.is_builtin_name(name) {
+            return Err(NameError::BuiltinName);
+        }
+        if self.normname_symbols.contains_key(&name.trim().to_uppercase()) {
+            return Err(NameError::AlreadyUsed);
+        }
+        return Ok(());
+    }
+
+    // Whether the name refers to a builtin module in this scope or any parent
+    fn is_builtin_name(&self, name: &str) -> bool {
+        match self.lookup_by_name(name.to_string()) {
+            Some(symbol) => is_pointer(*symbol) && (*symbol & PAYLOAD_MASK) < APP_SYMBOL_START,
+            None => false
+        }
+    }
+
     pub fn is_valid_name(&self, name: String) -> bool {
-        // TODO: Other naming criteria check
-        let uname = name.to_uppercase();
-        return self.normname_symbols.contains_key(&uname) == false;
+        return self.check_name(&name).is_ok();
     }
 
     pub fn lookup_by_name(&self, name: String) -> Option<&u64> {
         // get_name_symbol
         let norm_name = name.trim().to_uppercase();
-        return self.normname_symbols.get(&norm_name);
+        if let Some(symbol) = self.normname_symbols.get(&norm_name) {
+            return Some(symbol);
+        }
+        return self.parent()?.lookup_by_name(name);
     }
 
+    // Names visible from this scope, including builtins. Closest scope first.
+    // Sorted within each scope, so suggestions don't depend on hash order.
+    fn visible_names(&self, functions_only: bool) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        let mut current = Some(self);
+        while let Some(env) = current {
+            let mut scope_names: Vec<String> = Vec::new();
+            for symbol in env.normname_symbols.values() {
+                if let Some(ident) = env.lookup(*symbol) {
+                    let is_function = matches!(ident.value, Some(Atom::FunctionValue(_)));
+                    if let Some(name) = &ident.name {
+                        if is_function || !functions_only {
+                            scope_names.push(name.clone());
+                        }
+                    }
+                }
+            }
+            scope_names.sort();
+            names.append(&mut scope_names);
+            current = env.parent();
+        }
+        // Even when the prelude isn't loaded, so the user can be pointed to them
+        #[cfg(not(target_os = "unknown"))]
+        {
+            for module in BUILTIN_MODULES.iter() {
+                names.push(module.name.to_string());
+            }
+        }
+        return names;
+    }
+
+    // Similar names in scope, best first. For "did you mean" hints
+    pub fn suggest_names(&self, name: &str, limit: usize) -> Vec<String> {
+        let names = self.visible_names(false);
+        return rank_suggestions(name, names.iter().map(|name| name.as_str()), limit);
+    }
+
+    // PARSE_ERR_UNK_SYMBOL for a name that isn't defined, with suggestions
+    pub fn unknown_symbol(&self, name: &str) -> UnknownName {
+        return UnknownName {
+            code: PARSE_ERR_UNK_SYMBOL,
+            name: name.to_string(),
+            suggestions: self.suggest_names(name, MAX_SUGGESTIONS)
+        };
+    }
+
+    // RUNTIME_ERR_FN_UNK for a call to an undefined function. Only functions are suggested.
+    pub fn unknown_function(&self, name: &str) -> UnknownName {
+        let names = self.visible_names(true);
+        return UnknownName {
+            code: RUNTIME_ERR_FN_UNK,
+            name: name.to_string(),
+            suggestions: rank_suggestions(name, names.iter().map(|name| name.as_str()), MAX_SUGGESTIONS)
+        };
+    }
+
+    // Nearest definition of the symbol, searching outwards from this scope
     pub fn lookup(&self, symbol: u64) -> Option<&Identifier> {
-        return self.identifiers.get(&symbol)
+        if let Some(ident) = self.identifiers.get(&symbol) {
+            return Some(ident);
+        }
+        return self.parent()?.lookup(symbol);
     }
 
-    // Resolve a symbol to a terminal value by following pointers
-    // Terminates at a max depth
+    // Resolve a symbol to a terminal value by following pointers.
+    // None if it's undefined or part of a cycle. Use try_resolve to tell which.
     pub fn deep_resolve(&self, symbol: u64) -> Option<&Identifier> {
-        let mut count = 0;
+        return self.try_resolve(symbol).ok();
+    }
+
+    // Like deep_resolve, but reports every symbol in a pointer cycle, so they can all be flagged.
+    pub fn try_resolve(&self, symbol: u64) -> Result<&Identifier, ResolveError> {
+        // Symbol -> position in path
+        let mut visited: FnvHashMap<u64, usize> = FnvHashMap::default();
+        let mut path: Vec<u64> = Vec::new();
         let mut current_symbol = symbol;
 
-        while count < 1000 {
-            if let Some(ident) = self.lookup(current_symbol) {
-                
-                if let Some(value) = &ident.value {
-                    if let Atom::SymbolValue(next_symbol) = value {
-                        // Not a pointer, so terminal value
-                        if is_symbol(*next_symbol) {
-                            return Some(ident)
-                        }
+        loop {
+            let ident = match self.lookup(current_symbol) {
+                Some(ident) => ident,
+                None => return Err(ResolveError::Undefined(current_symbol))
+            };
+            let next_symbol = match &ident.value {
+                // Not a pointer, so terminal value. Errors share the pointer header bits.
+                Some(Atom::SymbolValue(next_symbol)) if is_symbol(*next_symbol) || is_error(*next_symbol) => return Ok(ident),
+                Some(Atom::SymbolValue(next_symbol)) => *next_symbol,
+                Some(_) => return Ok(ident),
+                None => return Err(ResolveError::Undefined(current_symbol))
+            };
 
-                        // Check for circular pointers
-                        if *next_symbol == current_symbol || *next_symbol == symbol {
-                            println!("Cyclic symbol reference");
-                            return None
-                        }
-                        current_symbol = *next_symbol
-                    }
-                    else {
-                        return Some(ident)
-                    }
-                } else {
-                    return None
+            visited.insert(current_symbol, path.len());
+            path.push(current_symbol);
+            if let Some(start) = visited.get(&next_symbol) {
+                // Symbols leading into the cycle aren't part of it
+                let symbols = path.split_off(*start);
+                let names = symbols.iter()
+                    .map(|symbol| self.lookup(*symbol).and_then(|ident| ident.name.clone()))
+                    .collect();
+                return Err(ResolveError::Circular(CircularReference { symbols: symbols, names: names }));
+            }
+            current_symbol = next_symbol;
+        }
+    }
+
+    // Capture the current names, values and expressions, i.e. before an edit for undo.
+    // Cheap, since the snapshot shares structure with the environment. Later changes copy
+    // only what they touch.
+    pub fn snapshot(&self) -> EnvironmentSnapshot {
+        return EnvironmentSnapshot {
+            normname_symbols: Rc::clone(&self.normname_symbols),
+            identifiers: self.identifiers.clone(),
+            field_symbols: Rc::clone(&self.field_symbols),
+            body: self.body.clone()
+        };
+    }
+
+    // Return to a snapshot, including previously computed results.
+    // Symbols that were freed since are taken back from the allocator so they aren't handed out twice.
+    pub fn restore(&mut self, snapshot: &EnvironmentSnapshot) {
+        self.normname_symbols = Rc::clone(&snapshot.normname_symbols);
+        self.identifiers = snapshot.identifiers.clone();
+        self.field_symbols = Rc::clone(&snapshot.field_symbols);
+        self.body = snapshot.body.clone();
+        let identifiers = &self.identifiers;
+        self.allocator.borrow_mut().retain_free(|symbol| !identifiers.contains_key(&symbol));
+    }
+
+    // Mark-and-sweep collection of values in this scope that nothing refers to anymore.
+    // i.e. intermediate strings from concatenation. Roots are named identifiers, interned fields,
+    // expressions and their results, and any extra values the caller still holds.
+    // Anything that looks like a pointer is treated as a reference, so live values are never freed.
+    pub fn collect_garbage(&mut self, extra_roots: &[u64]) -> GcStats {
+        let mut pending: Vec<u64> = extra_roots.to_vec();
+        for ident in self.identifiers.values() {
+            if ident.name.is_some() {
+                pending.push(ident.symbol);
+            }
+        }
+        pending.extend(self.field_symbols.values());
+        for expr in self.body.iter() {
+            pending.push(expr.symbol);
+            pending.extend(expr.result.iter());
+            pending.extend(expr.depends_on.iter());
+        }
+
+        let mut marked: FnvHashSet<u64> = FnvHashSet::default();
+        while let Some(value) = pending.pop() {
+            if !is_pointer(value) || !marked.insert(value) {
+                continue;
+            }
+            if let Some(ident) = self.identifiers.get(&value) {
+                if let Some(atom) = &ident.value {
+                    trace_atom(atom, &mut pending);
                 }
             }
+        }
 
-            count += 1;
+        let unreachable: Vec<u64> = self.identifiers.keys()
+            .filter(|symbol| !marked.contains(symbol))
+            .cloned()
+            .collect();
+        let mut allocator = self.allocator.borrow_mut();
+        for symbol in unreachable.iter() {
+            self.identifiers.remove(symbol);
+            // A local copy of a parent's identifier. The parent still owns the symbol.
+            let owned_by_parent = self.parent().and_then(|parent| parent.lookup(*symbol)).is_some();
+            if !owned_by_parent {
+                allocator.free(*symbol);
+            }
         }
-        return None
+
+        return GcStats {
+            live: self.identifiers.len(),
+            freed: unreachable.len()
+        };
     }
 }
 
 
+// Saved state of an Environment for undo and redo. See Environment::snapshot
+#[derive(Clone)]
+pub struct EnvironmentSnapshot {
+    normname_symbols: Rc<FnvHashMap<String, u64>>,
+    identifiers: PersistentMap<Identifier>,
+    field_symbols: Rc<FnvHashMap<String, u64>>,
+    body: Vec<Rc<Expression>>
+}
+
+#[derive(Debug,PartialEq,Clone,Copy)]
+pub struct GcStats {
+    // Values still held by this scope
+    pub live: usize,
+    // Values removed in this collection
+    pub freed: usize
+}
+
+// Queue the symbols an atom refers to
+fn trace_atom(atom: &Atom, pending: &mut Vec<u64>) {
+    match atom {
+        Atom::SymbolValue(symbol) => pending.push(*symbol),
+        Atom::ListValue(items) => pending.extend(items.iter()),
+        Atom::ObjectValue(obj) => {
+            pending.push(obj.id);
+            pending.push(obj.av_class);
+            if let Some(values) = &obj.av_values {
+                pending.extend(values.iter());
+            }
+        },
+        Atom::HashMapValue(map) => {
+            for (key, value) in map.table_iter() {
+                pending.push(key);
+                pending.push(value);
+            }
+        },
+        _ => {}
+    }
+}
+
+// Conditional keywords. Parsed by the compiler rather than being runtime symbols. See parser.js
+const RESERVED_WORDS: [&str; 2] = ["IF", "ELSE"];
+
+// Naming rules, independent of scope. Surrounding whitespace is ignored.
+// Names start with a unicode letter or underscore, followed by letters, digits or underscores.
+// Reserved words can't be redefined. Builtin names depend on the scope. See check_name
+pub fn validate_name(name: &str) -> Result<(), NameError> {
+    let name = name.trim();
+    let mut chars = name.chars();
+    match chars.next() {
+        None => return Err(NameError::Empty),
+        Some(first) if first.is_numeric() => return Err(NameError::LeadingDigit),
+        Some(first) if !(first.is_alphabetic() || first == '_') => return Err(NameError::InvalidCharacter(first)),
+        _ => {}
+    }
+    if let Some(invalid) = chars.find(|c| !(c.is_alphanumeric() || *c == '_')) {
+        return Err(NameError::InvalidCharacter(invalid));
+    }
+
+    let uname = name.to_uppercase();
+    if RESERVED_WORDS.contains(&uname.as_str()) {
+        return Err(NameError::ReservedWord);
+    }
+    #[cfg(not(target_os = "unknown"))]
+    {
+        if SYMBOL_ID_MAP.contains_key(&uname) {
+            return Err(NameError::ReservedWord);
+        }
+    }
+    return Ok(());
+}
+
+
 #[cfg(not(target_os = "unknown"))]
 impl fmt::Debug for Environment {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -176,3 +584,331 @@ impl fmt::Debug for Environment {
 }
 
 
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use crate::operators::__av_add;
+
+    #[test]
+    fn test_child_lookup() {
+        let mut global = Environment::new(APP_SYMBOL_START);
+        let rate = global.define_identifier().unwrap();
+        global.bind_name(rate, "rate".to_string()).unwrap();
+        global.bind_value(rate, Atom::NumericValue(0.5));
+        let global = Rc::new(global);
+
+        let mut child = Environment::new_child(Rc::clone(&global));
+        let total = child.define_identifier().unwrap();
+        assert_ne!(total, rate);
+        child.bind_name(total, "total".to_string()).unwrap();
+        assert_eq!(child.lookup_by_name("Rate".to_string()), Some(&rate));
+        assert_eq!(child.lookup_by_name("total".to_string()), Some(&total));
+        assert_eq!(global.lookup_by_name("total".to_string()), None);
+        assert_eq!(resolve_atom!(&child, rate), Atom::NumericValue(0.5));
+    }
+
+    #[test]
+    fn test_shadowing() {
+        let mut global = Environment::new(APP_SYMBOL_START);
+        let rate = global.define_identifier().unwrap();
+        global.bind_name(rate, "rate".to_string()).unwrap();
+        global.bind_value(rate, Atom::NumericValue(0.5));
+        let global = Rc::new(global);
+
+        let mut child = Environment::new_child(Rc::clone(&global));
+        assert!(child.is_valid_name("rate".to_string()));
+        let local_rate = child.define_identifier().unwrap();
+        child.bind_name(local_rate, "rate".to_string()).unwrap();
+        assert!(!child.is_valid_name("rate".to_string()));
+        assert_eq!(child.lookup_by_name("rate".to_string()), Some(&local_rate));
+
+        // Rebinding a parent's value only changes the child's view
+        child.bind_value(rate, Atom::NumericValue(2.0));
+        assert_eq!(resolve_atom!(&child, rate), Atom::NumericValue(2.0));
+        assert_eq!(resolve_atom!(&global, rate), Atom::NumericValue(0.5));
+        assert_eq!(child.lookup(rate).unwrap().name, Some("rate".to_string()));
+    }
+
+    #[test]
+    fn test_rename() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let price = env.define_identifier().unwrap();
+        env.bind_name(price, "price".to_string()).unwrap();
+        let tax = env.define_identifier().unwrap();
+        env.bind_name(tax, "tax".to_string()).unwrap();
+
+        // One name can't be bound to two symbols. The second keeps no name.
+        let other = env.define_identifier().unwrap();
+        assert_eq!(env.bind_name(other, "PRICE".to_string()), Err(PARSE_ERR_USED_NAME));
+        assert_eq!(env.lookup_by_name("price".to_string()), Some(&price));
+        assert_eq!(env.lookup(other).and_then(|ident| ident.name.clone()), None);
+        assert_eq!(env.bind_name(other, "unit price".to_string()), Err(PARSE_ERR_INVALID_NAME));
+        // Rebinding the same symbol can change the case
+        env.bind_name(tax, "Tax".to_string()).unwrap();
+        env.bind_name(tax, "tax".to_string()).unwrap();
+
+        let mut total = Expression::new(1, "price * 2".to_string());
+        total.symbol = env.define_identifier().unwrap();
+        total.depends_on.push(price);
+        let total_symbol = total.symbol;
+        env.body.push(Rc::new(total));
+
+        assert_eq!(env.rename(price, "Tax".to_string()), Err(PARSE_ERR_USED_NAME));
+        assert_eq!(env.rename(price, "cost".to_string()), Ok(vec![total_symbol]));
+        assert_eq!(env.lookup_by_name("price".to_string()), None);
+        assert_eq!(env.lookup_by_name("COST".to_string()), Some(&price));
+        assert_eq!(env.lookup(price).unwrap().name, Some("cost".to_string()));
+        assert_eq!(env.rename(price, "Cost".to_string()), Ok(vec![total_symbol]));
+        assert!(env.is_valid_name("price".to_string()));
+
+        assert_eq!(env.unbind_name(tax), Ok(vec![]));
+        assert_eq!(env.unbind_name(tax), Err(PARSE_ERR_UNK_SYMBOL));
+        assert_eq!(env.lookup_by_name("tax".to_string()), None);
+        assert_eq!(env.rename(tax, "".to_string()), Err(PARSE_ERR_INVALID_NAME));
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert_eq!(validate_name("total_2"), Ok(()));
+        assert_eq!(validate_name("  Größe "), Ok(()));
+        assert_eq!(validate_name("_hidden"), Ok(()));
+        assert_eq!(validate_name("価格"), Ok(()));
+        assert_eq!(validate_name("   "), Err(NameError::Empty));
+        assert_eq!(validate_name("2nd"), Err(NameError::LeadingDigit));
+        assert_eq!(validate_name("a-b"), Err(NameError::InvalidCharacter('-')));
+        assert_eq!(validate_name("unit price"), Err(NameError::InvalidCharacter(' ')));
+        assert_eq!(validate_name("true"), Err(NameError::ReservedWord));
+        assert_eq!(validate_name("AND"), Err(NameError::ReservedWord));
+        assert_eq!(validate_name("If"), Err(NameError::ReservedWord));
+        assert_eq!(validate_name("Sqrt"), Ok(()));
+        assert_eq!(NameError::LeadingDigit.error_code(), PARSE_ERR_INVALID_NAME);
+
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let price = env.define_identifier().unwrap();
+        env.bind_name(price, "price".to_string()).unwrap();
+        assert_eq!(env.check_name("PRICE"), Err(NameError::AlreadyUsed));
+        assert_eq!(env.rename(price, "none".to_string()), Err(PARSE_ERR_USED_NAME));
+        assert_eq!(env.rename(price, "9lives".to_string()), Err(PARSE_ERR_INVALID_NAME));
+    }
+
+    #[test]
+    fn test_shared_allocator() {
+        let global = Rc::new(Environment::new(APP_SYMBOL_START));
+        let mut child_a = Environment::new_child(Rc::clone(&global));
+        let mut child_b = Environment::new_child(Rc::clone(&global));
+        let a = child_a.define_identifier().unwrap();
+        let b = child_b.define_identifier().unwrap();
+        assert_ne!(a, b);
+
+        child_a.bind_value(a, Atom::NumericValue(1.0));
+        child_a.free_identifier(a);
+        assert!(child_a.lookup(a).is_none());
+        assert_eq!(child_b.define_identifier().unwrap(), a);
+
+        let mut doc = SymbolAllocator::new(APP_SYMBOL_START);
+        let mut small = Environment::with_allocator(Rc::new(RefCell::new(doc.reserve(1).unwrap())));
+        small.define_identifier().unwrap();
+        assert_eq!(small.define_identifier(), Err(RUNTIME_ERR_SYMBOL_OVERFLOW));
+        // Returned as the value, without binding anything to the error
+        assert_eq!(small.init_value(Atom::NumericValue(1.0)), RUNTIME_ERR_SYMBOL_OVERFLOW);
+        assert!(small.lookup(RUNTIME_ERR_SYMBOL_OVERFLOW).is_none());
+        assert_eq!(small.field_symbol("amount"), Err(RUNTIME_ERR_SYMBOL_OVERFLOW));
+    }
+
+    #[test]
+    fn test_collect_garbage() {
+        let mut env = Environment::new_empty(APP_SYMBOL_START);
+        let greeting = env.define_identifier().unwrap();
+        env.bind_name(greeting, "greeting".to_string()).unwrap();
+        let hello = env.init_value(Atom::StringValue("hello ".to_string()));
+        let world = env.init_value(Atom::StringValue("world".to_string()));
+        let joined = __av_add(&mut env, hello, world);
+        env.bind_value(greeting, Atom::SymbolValue(joined));
+
+        let item = env.init_value(Atom::StringValue("item".to_string()));
+        let list = env.init_value(Atom::ListValue(vec![item]));
+        let mut expr = Expression::new(1, "[\"item\"]".to_string());
+        expr.symbol = env.define_identifier().unwrap();
+        expr.result = Some(list);
+        env.body.push(Rc::new(expr));
+        let held = env.init_value(Atom::StringValue("in flight".to_string()));
+
+        // hello and world were only intermediate values
+        assert_eq!(env.collect_garbage(&[held]), GcStats { live: 5, freed: 2 });
+        assert!(env.lookup(hello).is_none());
+        assert_eq!(resolve_atom!(&env, greeting), Atom::StringValue("hello world".to_string()));
+        assert_eq!(resolve_atom!(&env, item), Atom::StringValue("item".to_string()));
+
+        assert_eq!(env.collect_garbage(&[]), GcStats { live: 4, freed: 1 });
+        // Freed symbols are reused
+        let reused = env.define_identifier().unwrap();
+        assert!(reused == hello || reused == world || reused == held);
+    }
+
+    #[test]
+    fn test_collect_garbage_child() {
+        let mut parent = Environment::new_empty(APP_SYMBOL_START);
+        let parent_tmp = parent.init_value(Atom::StringValue("parent".to_string()));
+        let mut child = Environment::new_child(Rc::new(parent));
+        child.bind_value(parent_tmp, Atom::StringValue("child".to_string()));
+
+        // The child's copy is dropped, but the symbol still belongs to the parent
+        assert_eq!(child.collect_garbage(&[]), GcStats { live: 0, freed: 1 });
+        assert_eq!(resolve_atom!(&child, parent_tmp), Atom::StringValue("parent".to_string()));
+        assert_ne!(child.define_identifier().unwrap(), parent_tmp);
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let price = env.define_identifier().unwrap();
+        env.bind_name(price, "price".to_string()).unwrap();
+        env.bind_value(price, Atom::NumericValue(10.0));
+        let mut expr = Expression::new(1, "10".to_string());
+        expr.symbol = price;
+        expr.result = Some(f64::to_bits(10.0));
+        env.body.push(Rc::new(expr));
+
+        let before = env.snapshot();
+        assert!(before.identifiers.ptr_eq(&env.identifiers));
+
+        // Edit the formula and its result, and add a new cell
+        Rc::make_mut(&mut env.body[0]).input = "20".to_string();
+        env.bind_value(price, Atom::NumericValue(20.0));
+        let tax = env.define_identifier().unwrap();
+        env.bind_name(tax, "tax".to_string()).unwrap();
+        env.rename(price, "cost".to_string()).unwrap();
+        let after = env.snapshot();
+
+        env.restore(&before);
+        assert_eq!(env.body[0].input, "10");
+        assert_eq!(resolve_atom!(&env, price), Atom::NumericValue(10.0));
+        assert_eq!(env.lookup_by_name("price".to_string()), Some(&price));
+        assert_eq!(env.lookup_by_name("tax".to_string()), None);
+
+        // Redo
+        env.restore(&after);
+        assert_eq!(env.body[0].input, "20");
+        assert_eq!(resolve_atom!(&env, price), Atom::NumericValue(20.0));
+        assert_eq!(env.lookup_by_name("tax".to_string()), Some(&tax));
+
+        // Values freed after a snapshot aren't reused once it's restored
+        let snapshot = env.snapshot();
+        env.free_identifier(tax);
+        env.restore(&snapshot);
+        assert_ne!(env.define_identifier().unwrap(), tax);
+    }
+
+    #[test]
+    fn test_prelude() {
+        let global = Environment::new(APP_SYMBOL_START);
+        assert_eq!(global.lookup_by_name("MIN".to_string()), Some(&AV_FN_MIN.symbol));
+        assert!(global.lookup(AV_FN_SQRT.symbol).unwrap().value == Some(AV_FN_SQRT.value.clone()));
+        assert_eq!(global.check_name("sqrt"), Err(NameError::BuiltinName));
+        // Every builtin gets its name
+        for module in BUILTIN_MODULES.iter() {
+            assert_eq!(global.lookup_by_name(module.name.to_string()), Some(&module.symbol), "{}", module.name);
+        }
+
+        let mut child = Environment::new_child(Rc::new(global));
+        assert_eq!(child.lookup_by_name("round".to_string()), Some(&AV_FN_ROUND.symbol));
+        assert_eq!(child.check_name("Round"), Err(NameError::BuiltinName));
+        // Not silently shadowed, even in a nested scope
+        let round = child.define_identifier().unwrap();
+        assert_eq!(child.bind_name(round, "round".to_string()), Err(PARSE_ERR_USED_NAME));
+        assert_eq!(child.lookup_by_name("round".to_string()), Some(&AV_FN_ROUND.symbol));
+        assert_eq!(child.lookup(round).and_then(|ident| ident.name.clone()), None);
+        let total = child.define_identifier().unwrap();
+        child.bind_name(total, "total".to_string()).unwrap();
+        assert_eq!(child.rename(total, "Sqrt".to_string()), Err(PARSE_ERR_USED_NAME));
+        assert_eq!(child.lookup_by_name("total".to_string()), Some(&total));
+
+        let mut empty = Environment::new_empty(APP_SYMBOL_START);
+        assert_eq!(empty.lookup_by_name("min".to_string()), None);
+        assert_eq!(empty.check_name("min"), Ok(()));
+        let min = empty.define_identifier().unwrap();
+        empty.bind_name(min, "min".to_string()).unwrap();
+        assert_eq!(empty.lookup_by_name("min".to_string()), Some(&min));
+        assert!(empty.new_isolated().lookup_by_name("abs".to_string()).is_some());
+    }
+
+    #[test]
+    fn test_circular_reference() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let a = env.define_identifier().unwrap();
+        let b = env.define_identifier().unwrap();
+        let c = env.define_identifier().unwrap();
+        let start = env.define_identifier().unwrap();
+        env.bind_name(a, "a".to_string()).unwrap();
+        env.bind_name(c, "c".to_string()).unwrap();
+        env.bind_value(a, Atom::SymbolValue(b));
+        env.bind_value(b, Atom::SymbolValue(c));
+        env.bind_value(c, Atom::SymbolValue(a));
+        env.bind_value(start, Atom::SymbolValue(b));
+
+        let expected = CircularReference {
+            symbols: vec![b, c, a],
+            names: vec![None, Some("c".to_string()), Some("a".to_string())]
+        };
+        assert_eq!(env.try_resolve(start).err(), Some(ResolveError::Circular(expected)));
+        assert!(env.deep_resolve(a).is_none());
+
+        // Breaking the cycle
+        env.bind_value(c, Atom::NumericValue(1.0));
+        assert_eq!(env.try_resolve(start).unwrap().symbol, c);
+        env.bind_value(c, Atom::SymbolValue(c + 100));
+        assert_eq!(env.try_resolve(a).err(), Some(ResolveError::Undefined(c + 100)));
+        assert_eq!(ResolveError::Undefined(c).error_code(), RUNTIME_ERR_UNK_VAL);
+    }
+
+    #[test]
+    fn test_unknown_name_suggestions() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let total = env.define_identifier().unwrap();
+        env.bind_name(total, "Total".to_string()).unwrap();
+        env.bind_value(total, Atom::NumericValue(1.0));
+        let mut child = Environment::new_child(Rc::new(env));
+        let totals = child.define_identifier().unwrap();
+        child.bind_name(totals, "totals".to_string()).unwrap();
+
+        assert_eq!(child.suggest_names("Totl", 3), vec!["Total".to_string(), "totals".to_string()]);
+        let err = child.unknown_symbol("Totl");
+        assert_eq!(err.error_code(), PARSE_ERR_UNK_SYMBOL);
+        assert_eq!(err.message(), "Arevel didn't recognize the name \"Totl\". Did you mean \"Total\" or \"totals\"?");
+        assert!(child.unknown_symbol("zzzzzz").suggestions.is_empty());
+
+        // Only functions, including builtins when the prelude isn't loaded
+        let err = Environment::new_empty(APP_SYMBOL_START).unknown_function("mn");
+        assert_eq!(err.error_code(), RUNTIME_ERR_FN_UNK);
+        assert_eq!(err.suggestions[0], "min");
+        assert!(!child.unknown_function("Totl").suggestions.contains(&"Total".to_string()));
+
+        // Sorted within each scope, whatever order the names were added in
+        let mut env = Environment::new_empty(APP_SYMBOL_START);
+        for name in ["tax", "price", "cost", "rate"].iter() {
+            let symbol = env.define_identifier().unwrap();
+            env.bind_name(symbol, name.to_string()).unwrap();
+        }
+        let names = env.visible_names(false);
+        assert_eq!(&names[..4], &["cost".to_string(), "price".to_string(), "rate".to_string(), "tax".to_string()]);
+    }
+
+    #[test]
+    fn test_isolated_scope() {
+        let mut global = Environment::new(APP_SYMBOL_START);
+        let rate = global.define_identifier().unwrap();
+        global.bind_name(rate, "rate".to_string()).unwrap();
+        global.bind_value(rate, Atom::NumericValue(0.5));
+        let field = global.field_symbol("amount").unwrap();
+
+        let mut pure = global.new_isolated();
+        assert!(pure.parent().is_none());
+        assert!(pure.lookup(rate).is_none());
+        assert_eq!(pure.lookup_by_name("rate".to_string()), None);
+        assert_eq!(pure.field_symbol("Amount").unwrap(), field);
+        assert_ne!(pure.define_identifier().unwrap(), rate);
+    }
+}
@@ -51,6 +51,16 @@ impl Expression {
         }
     }
 
+    // Merge in symbols this expression may read. Conditionals add every branch's
+    // references, not just the taken one, so the list stays conservative. See Conditional::new
+    pub fn add_dependencies(&mut self, symbols: &[u64]) {
+        for symbol in symbols.iter() {
+            if !self.depends_on.contains(symbol) {
+                self.depends_on.push(*symbol);
+            }
+        }
+    }
+
     pub fn set_result(&mut self, result: u64) {
         // Set result only if it wasn't previously set to avoid clobbering errors.
         if self.result.is_none() {
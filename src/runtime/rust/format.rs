@@ -1,4 +1,7 @@
 use crate::structs::Atom;
+use crate::environment::Environment;
+use crate::constants::SYMBOL_EMPTY_ARR;
+use crate::types::{is_number, is_pointer, is_error};
 use core::fmt;
 use fnv::FnvHashMap;
 
@@ -37,17 +40,37 @@ pub fn repr_atom(atom: &Atom) -> String {
         Atom::NumericValue(num) => {
             format!("{}", repr_float(*num))
         },
+        Atom::DecimalValue(dec) => {
+            format!("{}", dec)
+        },
         Atom::StringValue(str_val) => {
             format!("{}", str_val)      // "\"{}\""
         }
+        Atom::DateValue(date) => {
+            format!("{}", date)
+        },
+        Atom::DateTimeValue(datetime) => {
+            format!("{}", datetime)
+        },
+        Atom::DurationValue(duration) => {
+            format!("{}", duration)
+        },
         Atom::SymbolValue(symbol) => {
             repr_symbol(symbol)
         },
+        Atom::ListValue(items) => {
+            // Without an environment, nested pointers can't be resolved. See repr_value.
+            let parts: Vec<String> = items.iter().map(|item| repr_raw(*item)).collect();
+            format!("[{}]", parts.join(", "))
+        },
         Atom::ObjectValue(obj_val) => {
             format!("{}", obj_val.id)
         },
         Atom::HashMapValue(map_val) => {
-            format!("{:#?}", map_val)
+            let parts: Vec<String> = map_val.table_iter()
+                .map(|(key, value)| format!("{}: {}", repr_raw(key), repr_raw(value)))
+                .collect();
+            format!("{{{}}}", parts.join(", "))
         },
         Atom::FunctionValue(fn_val) => {
             format!("<Function>")
@@ -55,6 +78,41 @@ pub fn repr_atom(atom: &Atom) -> String {
     }
 }
 
+// Format a value without looking up pointers.
+#[cfg(not(target_os = "unknown"))]
+fn repr_raw(value: u64) -> String {
+    if is_number(value) {
+        return repr_number(value);
+    } else if value == SYMBOL_EMPTY_ARR {
+        return String::from("[]");
+    }
+    return repr_symbol(&value);
+}
+
+// Format a value, following pointers to heap values such as strings and list elements.
+#[cfg(not(target_os = "unknown"))]
+pub fn repr_value(env: &Environment, value: u64) -> String {
+    if is_pointer(value) && !is_error(value) {
+        if let Some(ident) = env.deep_resolve(value) {
+            match &ident.value {
+                Some(Atom::ListValue(items)) => {
+                    let parts: Vec<String> = items.iter().map(|item| repr_value(env, *item)).collect();
+                    return format!("[{}]", parts.join(", "));
+                },
+                Some(Atom::HashMapValue(map)) => {
+                    let parts: Vec<String> = map.table_iter()
+                        .map(|(key, value)| format!("{}: {}", repr_value(env, key), repr_value(env, value)))
+                        .collect();
+                    return format!("{{{}}}", parts.join(", "));
+                },
+                Some(atom) => return repr_atom(atom),
+                None => {}
+            }
+        }
+    }
+    return repr_raw(value);
+}
+
 #[cfg(not(target_os = "unknown"))]
 pub fn fmt_symbols_map(map: &FnvHashMap<u64, Atom>) -> String {
     let mut output = vec![];
@@ -1,21 +1,60 @@
 use crate::environment::Environment;
-use crate::structs::Atom;
+use crate::structs::{Atom, ValueType};
 use crate::constants::*;
 use crate::types::*;
+use crate::decimal::*;
+use crate::temporal::*;
+use crate::operators::{as_list, as_text, as_decimal, total_order, __av_list};
+use core::cmp::Ordering;
 
 
 #[derive(Clone)]
 pub enum NativeFn {
+    Fn0(NativeFn0),
     Fn1(NativeFn1),
     Fn2(NativeFn2),
     Fn3(NativeFn3),
-    // Fn4(NativeFn4),
+    Fn4(NativeFn4),
     // Fn5(NativeFn5),
 }
 
+// Functions are equal if they're the same native function.
 impl PartialEq for NativeFn {
     fn eq(&self, other: &Self) -> bool {
-        return false;
+        match (self, other) {
+            (NativeFn::Fn0(f_a), NativeFn::Fn0(f_b)) => f_a.func as usize == f_b.func as usize,
+            (NativeFn::Fn1(f_a), NativeFn::Fn1(f_b)) => f_a.func as usize == f_b.func as usize,
+            (NativeFn::Fn2(f_a), NativeFn::Fn2(f_b)) => f_a.func as usize == f_b.func as usize,
+            (NativeFn::Fn3(f_a), NativeFn::Fn3(f_b)) => f_a.func as usize == f_b.func as usize,
+            (NativeFn::Fn4(f_a), NativeFn::Fn4(f_b)) => f_a.func as usize == f_b.func as usize,
+            _ => false
+        }
+    }
+}
+
+impl NativeFn {
+    pub fn arity(&self) -> usize {
+        match self {
+            NativeFn::Fn0(_) => 0,
+            NativeFn::Fn1(_) => 1,
+            NativeFn::Fn2(_) => 2,
+            NativeFn::Fn3(_) => 3,
+            NativeFn::Fn4(_) => 4
+        }
+    }
+
+    // Call with evaluated arguments. RUNTIME_ERR_FN_ARITY if the count doesn't match.
+    pub fn call(&self, env: &mut Environment, args: Vec<u64>) -> u64 {
+        if args.len() != self.arity() {
+            return RUNTIME_ERR_FN_ARITY;
+        }
+        match self {
+            NativeFn::Fn0(func) => func.call(env, args),
+            NativeFn::Fn1(func) => func.call(env, args),
+            NativeFn::Fn2(func) => func.call(env, args),
+            NativeFn::Fn3(func) => func.call(env, args),
+            NativeFn::Fn4(func) => func.call(env, args)
+        }
     }
 }
 
@@ -23,6 +62,19 @@ trait Callable {
     fn call(&self, env: &mut Environment, args: Vec<u64>) -> u64;
 }
 
+#[derive(Clone)]
+pub struct NativeFn0 {
+    pub func: fn(&mut Environment) -> u64
+}
+
+impl NativeFn0 {
+    pub const fn create_atom(func: fn(&mut Environment) -> u64) -> Atom {
+        return Atom::FunctionValue(NativeFn::Fn0(NativeFn0 {
+            func: func
+        }))
+    }
+}
+
 #[derive(Clone)]
 pub struct NativeFn1 {
     pub func: fn(&mut Environment, u64) -> u64
@@ -55,6 +107,34 @@ pub struct NativeFn3 {
     pub func: fn(&mut Environment, u64, u64, u64) -> u64
 }
 
+impl NativeFn3 {
+    pub const fn create_atom(func: fn(&mut Environment, u64, u64, u64) -> u64) -> Atom {
+        return Atom::FunctionValue(NativeFn::Fn3(NativeFn3 {
+            func: func
+        }))
+    }
+}
+
+#[derive(Clone)]
+pub struct NativeFn4 {
+    pub func: fn(&mut Environment, u64, u64, u64, u64) -> u64
+}
+
+impl NativeFn4 {
+    pub const fn create_atom(func: fn(&mut Environment, u64, u64, u64, u64) -> u64) -> Atom {
+        return Atom::FunctionValue(NativeFn::Fn4(NativeFn4 {
+            func: func
+        }))
+    }
+}
+
+
+impl Callable for NativeFn0 {
+    fn call(&self, mut env: &mut Environment, _args: Vec<u64>) -> u64 {
+        // TODO: Check arity
+        return (self.func)(&mut env);
+    }
+}
 
 impl Callable for NativeFn1 {
     fn call(&self, mut env: &mut Environment, args: Vec<u64>) -> u64 {
@@ -72,51 +152,433 @@ impl Callable for NativeFn2 {
 
 impl Callable for NativeFn3 {
     fn call(&self, mut env: &mut Environment, args: Vec<u64>) -> u64 {
-        // TODO: Check arity
         return (self.func)(&mut env, args[0], args[1], args[2])
     }
 }
 
-pub fn __av_min(_env: &mut Environment, a: u64, b: u64) -> u64 {
-    let f_a: f64 = valid_num!(a);
-	let f_b: f64 = valid_num!(b);
+impl Callable for NativeFn4 {
+    fn call(&self, mut env: &mut Environment, args: Vec<u64>) -> u64 {
+        return (self.func)(&mut env, args[0], args[1], args[2], args[3])
+    }
+}
+
+// b if it's ordered towards pick from a, otherwise a. Exact when either is a decimal.
+fn pick_number(env: &mut Environment, a: u64, b: u64, pick: Ordering) -> u64 {
+    propagate_errors!(a, b);
+    let atom_a = resolve_atom!(env, a);
+    let atom_b = resolve_atom!(env, b);
+    if let (Atom::DecimalValue(_), _) | (_, Atom::DecimalValue(_)) = (&atom_a, &atom_b) {
+        return match (as_decimal(&atom_a), as_decimal(&atom_b)) {
+            (Some(dec_a), Some(dec_b)) => if dec_b.cmp(&dec_a) == pick { b } else { a },
+            _ => RUNTIME_ERR_EXPECTED_NUM
+        };
+    }
+    match (atom_a, atom_b) {
+        (Atom::NumericValue(f_a), Atom::NumericValue(f_b)) if !is_nan(f_a) && !is_nan(f_b) => {
+            return if f_b.partial_cmp(&f_a) == Some(pick) { f_b.to_bits() } else { f_a.to_bits() };
+        },
+        _ => return RUNTIME_ERR_EXPECTED_NUM
+    }
+}
 
-    return f_a.min(f_b).to_bits();
+pub fn __av_min(env: &mut Environment, a: u64, b: u64) -> u64 {
+    return pick_number(env, a, b, Ordering::Less);
 }
 
-pub fn __av_max(_env: &mut Environment, a: u64, b: u64) -> u64 {
-    let f_a: f64 = valid_num!(a);
-	let f_b: f64 = valid_num!(b);
+pub fn __av_max(env: &mut Environment, a: u64, b: u64) -> u64 {
+    return pick_number(env, a, b, Ordering::Greater);
+}
 
-    return f_a.max(f_b).to_bits();
+// Apply a rounding mode to decimal values. Returns None for other value types.
+fn round_decimal(env: &mut Environment, a: u64, places: u32, mode: RoundingMode) -> Option<u64> {
+    if let Atom::DecimalValue(dec) = resolve_atom!(env, a) {
+        return match dec.round(places, mode) {
+            Some(result) => Some(env.init_value(Atom::DecimalValue(result))),
+            None => Some(RUNTIME_ERR_NUM_OVERFLOW)
+        };
+    }
+    return None;
 }
 
-pub fn __av_abs(_env: &mut Environment, a: u64) -> u64 {
+pub fn __av_abs(env: &mut Environment, a: u64) -> u64 {
+    propagate_errors!(a);
+    if let Atom::DecimalValue(dec) = resolve_atom!(env, a) {
+        return env.init_value(Atom::DecimalValue(dec.abs()));
+    }
     let f_a: f64 = valid_num!(a);
     return f_a.abs().to_bits();
 }
 
-pub fn __av_ceil(_env: &mut Environment, a: u64) -> u64 {
+pub fn __av_ceil(env: &mut Environment, a: u64) -> u64 {
+    propagate_errors!(a);
+    if let Some(result) = round_decimal(env, a, 0, RoundingMode::Ceiling) {
+        return result;
+    }
     let f_a: f64 = valid_num!(a);
     return f_a.ceil().to_bits();
 }
 
-pub fn __av_floor(_env: &mut Environment, a: u64) -> u64 {
+pub fn __av_floor(env: &mut Environment, a: u64) -> u64 {
+    propagate_errors!(a);
+    if let Some(result) = round_decimal(env, a, 0, RoundingMode::Floor) {
+        return result;
+    }
     let f_a: f64 = valid_num!(a);
     return f_a.floor().to_bits();
 }
 
-pub fn __av_truncate(_env: &mut Environment, a: u64) -> u64 {
+pub fn __av_truncate(env: &mut Environment, a: u64) -> u64 {
+    propagate_errors!(a);
+    if let Some(result) = round_decimal(env, a, 0, RoundingMode::Down) {
+        return result;
+    }
     let f_a: f64 = valid_num!(a);
     return f_a.trunc().to_bits();
 }
 
-pub fn __av_round(_env: &mut Environment, a: u64) -> u64 {
+pub fn __av_round(env: &mut Environment, a: u64) -> u64 {
+    propagate_errors!(a);
+    // Half away from zero, matching f64::round
+    if let Some(result) = round_decimal(env, a, 0, RoundingMode::HalfUp) {
+        return result;
+    }
     let f_a: f64 = valid_num!(a);
     return f_a.round().to_bits();
 }
 
-pub fn __av_sqrt(_env: &mut Environment, a: u64) -> u64 {
+// Whole number of decimal places, up to DECIMAL_MAX_SCALE
+fn decimal_places(places: f64) -> Option<u32> {
+    if places.fract() != 0.0 || places < 0.0 || places > DECIMAL_MAX_SCALE as f64 {
+        return None;
+    }
+    return Some(places as u32);
+}
+
+pub fn __av_round_to(env: &mut Environment, a: u64, places: u64) -> u64 {
+    propagate_errors!(a, places);
+    let f_places: f64 = valid_num!(places);
+    let places = match decimal_places(f_places) {
+        Some(places) => places,
+        None => return RUNTIME_ERR_INVALID_TYPE
+    };
+    if let Some(result) = round_decimal(env, a, places, RoundingMode::HalfUp) {
+        return result;
+    }
+    let f_a: f64 = valid_num!(a);
+    let factor = 10f64.powi(places as i32);
+    return ((f_a * factor).round() / factor).to_bits();
+}
+
+// Exact division, rounded once to the given places. div(1, 3, 2, "half_up") = 0.33
+// `/` keeps DECIMAL_DIV_PLACES with banker's rounding, so rounding that again can be off by one.
+pub fn __av_div_round(env: &mut Environment, a: u64, b: u64, places: u64, mode: u64) -> u64 {
+    propagate_errors!(a, b, places, mode);
+    let f_places: f64 = valid_num!(places);
+    let places = match decimal_places(f_places) {
+        Some(places) => places,
+        None => return RUNTIME_ERR_INVALID_TYPE
+    };
+    let mode = match as_text(env, mode) {
+        Some(name) => match RoundingMode::from_name(&name) {
+            Some(mode) => mode,
+            None => return RUNTIME_ERR_INVALID_TYPE
+        },
+        None => return RUNTIME_ERR_EXPECTED_STR
+    };
+    let dec_a = as_decimal(&resolve_atom!(env, a));
+    let dec_b = as_decimal(&resolve_atom!(env, b));
+    let (dec_a, dec_b) = match (dec_a, dec_b) {
+        (Some(dec_a), Some(dec_b)) => (dec_a, dec_b),
+        _ => return RUNTIME_ERR_EXPECTED_NUM
+    };
+    if dec_b.is_zero() {
+        return RUNTIME_ERR_DIV_Z;
+    }
+    match dec_a.checked_div(&dec_b, places, mode) {
+        Some(result) => return env.init_value(Atom::DecimalValue(result)),
+        None => return RUNTIME_ERR_NUM_OVERFLOW
+    }
+}
+
+// Decimals are converted to floats, since roots are rarely exact
+pub fn __av_sqrt(env: &mut Environment, a: u64) -> u64 {
+    propagate_errors!(a);
+    if let Atom::DecimalValue(dec) = resolve_atom!(env, a) {
+        return dec.to_f64().sqrt().to_bits();
+    }
     let f_a: f64 = valid_num!(a);
     return f_a.sqrt().to_bits();
 }
+
+// Convert a number or text to an exact decimal.
+pub fn __av_decimal(env: &mut Environment, a: u64) -> u64 {
+    propagate_errors!(a);
+    let dec = match resolve_atom!(env, a) {
+        Atom::DecimalValue(_) => return a,
+        Atom::NumericValue(num) => {
+            if is_nan(num) {
+                return RUNTIME_ERR_EXPECTED_NUM
+            }
+            Decimal::from_f64(num)
+        },
+        Atom::StringValue(str_val) => Decimal::parse(&str_val),
+        _ => return RUNTIME_ERR_EXPECTED_NUM
+    };
+
+    match dec {
+        Some(result) => return env.init_value(Atom::DecimalValue(result)),
+        None => return PARSE_ERR_INVALID_FLOAT
+    }
+}
+
+
+pub fn __av_date(env: &mut Environment, year: u64, month: u64, day: u64) -> u64 {
+    propagate_errors!(year, month, day);
+    let i_year = valid_int!(year);
+    let i_month = valid_int!(month);
+    let i_day = valid_int!(day);
+    if !(1..=12).contains(&i_month) || !(1..=31).contains(&i_day) {
+        return RUNTIME_ERR_INVALID_DATE;
+    }
+    match Date::from_ymd(i_year, i_month as u32, i_day as u32) {
+        Some(date) => return env.init_value(Atom::DateValue(date)),
+        None => return RUNTIME_ERR_INVALID_DATE
+    }
+}
+
+pub fn __av_today(env: &mut Environment) -> u64 {
+    let date = Date::today(env.clock);
+    return env.init_value(Atom::DateValue(date));
+}
+
+pub fn __av_weekday(env: &mut Environment, a: u64) -> u64 {
+    propagate_errors!(a);
+    match resolve_atom!(env, a) {
+        Atom::DateValue(date) => return (date.weekday() as f64).to_bits(),
+        Atom::DateTimeValue(datetime) => return (datetime.date().weekday() as f64).to_bits(),
+        _ => return RUNTIME_ERR_INVALID_TYPE
+    }
+}
+
+pub fn __av_add_days(env: &mut Environment, a: u64, days: u64) -> u64 {
+    propagate_errors!(a, days);
+    let i_days = valid_int!(days);
+    let result = match resolve_atom!(env, a) {
+        Atom::DateValue(date) => match date.add_days(i_days) {
+            Some(result) => Atom::DateValue(result),
+            None => return RUNTIME_ERR_NUM_OVERFLOW
+        },
+        Atom::DateTimeValue(datetime) => {
+            match Duration::from_days(i_days).and_then(|duration| datetime.add(&duration)) {
+                Some(result) => Atom::DateTimeValue(result),
+                None => return RUNTIME_ERR_NUM_OVERFLOW
+            }
+        },
+        _ => return RUNTIME_ERR_INVALID_TYPE
+    };
+    return env.init_value(result);
+}
+
+// Number of days from start to end. Fractional for datetimes.
+pub fn __av_diff(env: &mut Environment, start: u64, end: u64) -> u64 {
+    propagate_errors!(start, end);
+    let start_dt = match resolve_atom!(env, start) {
+        Atom::DateValue(date) => match date.to_datetime() {
+            Some(datetime) => datetime,
+            None => return RUNTIME_ERR_NUM_OVERFLOW
+        },
+        Atom::DateTimeValue(datetime) => datetime,
+        _ => return RUNTIME_ERR_INVALID_TYPE
+    };
+    let end_dt = match resolve_atom!(env, end) {
+        Atom::DateValue(date) => match date.to_datetime() {
+            Some(datetime) => datetime,
+            None => return RUNTIME_ERR_NUM_OVERFLOW
+        },
+        Atom::DateTimeValue(datetime) => datetime,
+        _ => return RUNTIME_ERR_INVALID_TYPE
+    };
+    match end_dt.since(&start_dt) {
+        Some(duration) => return (duration.millis as f64 / MS_PER_DAY as f64).to_bits(),
+        None => return RUNTIME_ERR_NUM_OVERFLOW
+    }
+}
+
+// Duration of the given number of days. i.e. date + days(7)
+pub fn __av_days(env: &mut Environment, a: u64) -> u64 {
+    propagate_errors!(a);
+    let f_a: f64 = valid_num!(a);
+    let millis = (f_a * MS_PER_DAY as f64).round();
+    if millis.abs() >= i64::MAX as f64 {
+        return RUNTIME_ERR_NUM_OVERFLOW;
+    }
+    return env.init_value(Atom::DurationValue(Duration { millis: millis as i64 }));
+}
+
+// Number of elements in a list, or characters in text
+pub fn __av_len(env: &mut Environment, a: u64) -> u64 {
+    propagate_errors!(a);
+    if let Some(items) = as_list(env, a) {
+        return (items.len() as f64).to_bits();
+    }
+    match resolve_atom!(env, a) {
+        Atom::StringValue(str_val) => return (str_val.chars().count() as f64).to_bits(),
+        _ => return RUNTIME_ERR_EXPECTED_LIST
+    }
+}
+
+// Sub-list from start up to, but not including, end. Negative positions count from the end.
+// Out of range positions are clamped, so slice(x, 0, 100) returns the whole list.
+pub fn __av_slice(env: &mut Environment, a: u64, start: u64, end: u64) -> u64 {
+    propagate_errors!(a, start, end);
+    let i_start = valid_int!(start);
+    let i_end = valid_int!(end);
+
+    let clamp = |index: i64, len: usize| -> usize {
+        let position = if index < 0 { index + len as i64 } else { index };
+        return position.max(0).min(len as i64) as usize;
+    };
+
+    if let Some(items) = as_list(env, a) {
+        let from = clamp(i_start, items.len());
+        let to = clamp(i_end, items.len()).max(from);
+        return __av_list(env, items[from..to].to_vec());
+    }
+    match resolve_atom!(env, a) {
+        Atom::StringValue(str_val) => {
+            let chars: Vec<char> = str_val.chars().collect();
+            let from = clamp(i_start, chars.len());
+            let to = clamp(i_end, chars.len()).max(from);
+            if from == to {
+                return SYMBOL_EMPTY_STR;
+            }
+            return env.init_value(Atom::StringValue(chars[from..to].iter().collect()));
+        },
+        _ => return RUNTIME_ERR_EXPECTED_LIST
+    }
+}
+
+// Name of the value's type. i.e. "number", "text", "list"
+pub fn __av_type_name(env: &mut Environment, a: u64) -> u64 {
+    let name = __av_type(env, a).name();
+    return env.init_value(Atom::StringValue(name.to_string()));
+}
+
+// Numbers and decimals
+pub fn __av_is_number(env: &mut Environment, a: u64) -> u64 {
+    match __av_type(env, a) {
+        ValueType::NumericType | ValueType::DecimalType => return SYMBOL_TRUE.symbol,
+        _ => return SYMBOL_FALSE.symbol
+    }
+}
+
+pub fn __av_is_text(env: &mut Environment, a: u64) -> u64 {
+    return __repr_bool(__av_type(env, a) == ValueType::StringType);
+}
+
+pub fn __av_is_error(_env: &mut Environment, a: u64) -> u64 {
+    return __repr_bool(is_error(a));
+}
+
+// The fallback when the value is an error. iferror(total / count, "n/a")
+pub fn __av_iferror(_env: &mut Environment, a: u64, fallback: u64) -> u64 {
+    if is_error(a) {
+        return fallback;
+    }
+    return a;
+}
+
+// Catch only one kind of error, given by an example of it. Other errors pass through.
+// The origin cell is ignored when matching. catch(total / count, 1 / 0, 0)
+pub fn __av_catch(_env: &mut Environment, a: u64, code: u64, fallback: u64) -> u64 {
+    if is_error(a) && is_error(code) && error_code(a) == error_code(code) {
+        return fallback;
+    }
+    return a;
+}
+
+fn repr_ordering(ordering: Ordering) -> u64 {
+    let result: f64 = match ordering {
+        Ordering::Less => -1.0,
+        Ordering::Equal => 0.0,
+        Ordering::Greater => 1.0
+    };
+    return result.to_bits();
+}
+
+// -1, 0 or 1. Any two values can be compared. See total_order for the order between types.
+pub fn __av_compare(env: &mut Environment, a: u64, b: u64) -> u64 {
+    propagate_errors!(a, b);
+    return repr_ordering(total_order(env, a, b, false));
+}
+
+// Like compare, with text compared case-insensitively. compare_ignore_case("a", "A") = 0
+pub fn __av_compare_ignore_case(env: &mut Environment, a: u64, b: u64) -> u64 {
+    propagate_errors!(a, b);
+    return repr_ordering(total_order(env, a, b, true));
+}
+
+// Function passed as a value. i.e. abs in map(list, abs)
+fn as_function(env: &Environment, value: u64) -> Option<NativeFn> {
+    match env.deep_resolve(value).and_then(|ident| ident.value.as_ref()) {
+        Some(Atom::FunctionValue(func)) => Some(func.clone()),
+        _ => None
+    }
+}
+
+// Apply a one-parameter function to each element. Stops at the first error.
+pub fn __av_map(env: &mut Environment, a: u64, func: u64) -> u64 {
+    propagate_errors!(a, func);
+    let items = match as_list(env, a) {
+        Some(items) => items,
+        None => return RUNTIME_ERR_EXPECTED_LIST
+    };
+    let func = match as_function(env, func) {
+        Some(func) => func,
+        None => return RUNTIME_ERR_FN_EXPECTED
+    };
+    let mut results: Vec<u64> = Vec::with_capacity(items.len());
+    for item in items {
+        let result = func.call(env, vec![item]);
+        if is_error(result) {
+            return result;
+        }
+        results.push(result);
+    }
+    return __av_list(env, results);
+}
+
+// Elements for which the function returns a truthy value
+pub fn __av_filter(env: &mut Environment, a: u64, func: u64) -> u64 {
+    propagate_errors!(a, func);
+    let items = match as_list(env, a) {
+        Some(items) => items,
+        None => return RUNTIME_ERR_EXPECTED_LIST
+    };
+    let func = match as_function(env, func) {
+        Some(func) => func,
+        None => return RUNTIME_ERR_FN_EXPECTED
+    };
+    let mut kept: Vec<u64> = Vec::new();
+    for item in items {
+        let keep = func.call(env, vec![item]);
+        if is_error(keep) {
+            return keep;
+        }
+        if __av_truthy(env, keep) {
+            kept.push(item);
+        }
+    }
+    return __av_list(env, kept);
+}
+
+// Stable ascending sort of a list, using the same order as compare.
+pub fn __av_sort(env: &mut Environment, a: u64) -> u64 {
+    match as_list(env, a) {
+        Some(mut items) => {
+            items.sort_by(|x, y| total_order(env, *x, *y, false));
+            return __av_list(env, items);
+        },
+        None => return RUNTIME_ERR_EXPECTED_LIST
+    }
+}
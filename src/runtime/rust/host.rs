@@ -0,0 +1,292 @@
+// Reading values out of the runtime, for hosts and embedders.
+// get_value returns resolved Atoms for Rust callers. The __av_*_encoded functions return a
+// flat buffer of u64 words, so C and WASM hosts can read results without the flatbuffer dump.
+//
+// Encoding. Every buffer starts with its total length in words, followed by one encoded value.
+//   [AV_ENC_NUMBER, f64 bits]
+//   [AV_ENC_TEXT, byte length, utf-8 bytes packed 8 per word, first byte in the lowest bits]
+//   [AV_ENC_DECIMAL, byte length, packed utf-8 of the decimal text. i.e. "0.10"]
+//   [AV_ENC_DATE, days since 1970-01-01 as i64]
+//   [AV_ENC_DATETIME, milliseconds since the epoch as i64]
+//   [AV_ENC_DURATION, milliseconds as i64]
+//   [AV_ENC_LIST, count, encoded elements...]
+//   [AV_ENC_OBJECT, class symbol, count, (field symbol, encoded value)...]
+//   [AV_ENC_MAP, count, (key symbol, encoded value)...]
+//   [AV_ENC_FUNCTION, symbol]
+//   [AV_ENC_ERROR, error value]
+//   [AV_ENC_SYMBOL, raw value]            True, False, None and other keywords
+
+use crate::environment::Environment;
+use crate::structs::{Atom, ResolveError};
+use crate::constants::*;
+use crate::types::*;
+use crate::operators::{as_text, as_list};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::slice;
+
+
+pub const AV_ENC_NUMBER: u64 = 1;
+pub const AV_ENC_TEXT: u64 = 2;
+pub const AV_ENC_DECIMAL: u64 = 3;
+pub const AV_ENC_DATE: u64 = 4;
+pub const AV_ENC_DATETIME: u64 = 5;
+pub const AV_ENC_DURATION: u64 = 6;
+pub const AV_ENC_LIST: u64 = 7;
+pub const AV_ENC_OBJECT: u64 = 8;
+pub const AV_ENC_MAP: u64 = 9;
+pub const AV_ENC_FUNCTION: u64 = 10;
+pub const AV_ENC_ERROR: u64 = 11;
+pub const AV_ENC_SYMBOL: u64 = 12;
+
+// Nested values deeper than this are encoded as a circular reference error
+const MAX_ENCODE_DEPTH: usize = 64;
+
+
+// Resolved value of a symbol. Errors are returned as Err, including errors stored as results.
+pub fn get_value(env: &Environment, symbol: u64) -> Result<Atom, u64> {
+    if is_error(symbol) {
+        return Err(symbol);
+    }
+    if let Some(text) = as_text(env, symbol) {
+        return Ok(Atom::StringValue(text));
+    }
+    if !is_pointer(symbol) {
+        return Ok(resolve_atom!(env, symbol));
+    }
+    match env.try_resolve(symbol) {
+        Ok(ident) => match &ident.value {
+            Some(Atom::SymbolValue(value)) if is_error(*value) => Err(*value),
+            Some(atom) => Ok(atom.clone()),
+            None => Err(RUNTIME_ERR_UNK_VAL)
+        },
+        // Tagged with a cell in the cycle. Use try_resolve for the full cycle.
+        Err(ResolveError::Circular(cycle)) => Err(with_error_origin(cycle.error_code(), cycle.symbols[0])),
+        Err(err) => Err(err.error_code())
+    }
+}
+
+// Case-insensitive, like other name lookups.
+pub fn get_value_by_name(env: &Environment, name: &str) -> Result<Atom, u64> {
+    match env.lookup_by_name(name.to_string()) {
+        Some(symbol) => get_value(env, *symbol),
+        None => Err(PARSE_ERR_UNK_SYMBOL)
+    }
+}
+
+
+fn encode_text(tag: u64, text: &str, out: &mut Vec<u64>) {
+    let bytes = text.as_bytes();
+    out.push(tag);
+    out.push(bytes.len() as u64);
+    for chunk in bytes.chunks(8) {
+        let mut word: u64 = 0;
+        for (i, byte) in chunk.iter().enumerate() {
+            word |= (*byte as u64) << (i * 8);
+        }
+        out.push(word);
+    }
+}
+
+fn encode_atom(env: &Environment, symbol: u64, atom: &Atom, out: &mut Vec<u64>, depth: usize) {
+    match atom {
+        Atom::NumericValue(num) => out.extend(&[AV_ENC_NUMBER, num.to_bits()]),
+        Atom::DecimalValue(dec) => encode_text(AV_ENC_DECIMAL, &dec.to_string(), out),
+        Atom::StringValue(text) => encode_text(AV_ENC_TEXT, text, out),
+        Atom::DateValue(date) => out.extend(&[AV_ENC_DATE, date.days as u64]),
+        Atom::DateTimeValue(datetime) => out.extend(&[AV_ENC_DATETIME, datetime.millis as u64]),
+        Atom::DurationValue(duration) => out.extend(&[AV_ENC_DURATION, duration.millis as u64]),
+        Atom::SymbolValue(value) => encode(env, *value, out, depth + 1),
+        Atom::ListValue(items) => {
+            out.extend(&[AV_ENC_LIST, items.len() as u64]);
+            for item in items.iter() {
+                encode(env, *item, out, depth + 1);
+            }
+        },
+        Atom::ObjectValue(obj) => {
+            out.push(AV_ENC_OBJECT);
+            out.push(obj.av_class);
+            if obj.is_table() {
+                out.push(obj.table_len() as u64);
+                for (field, value) in obj.table_iter() {
+                    out.push(field);
+                    encode(env, value, out, depth + 1);
+                }
+            } else {
+                // Positional values. Keyed by index
+                let values: &[u64] = match &obj.av_values {
+                    Some(values) => values,
+                    None => &[]
+                };
+                out.push(values.len() as u64);
+                for (index, value) in values.iter().enumerate() {
+                    out.push(index as u64);
+                    encode(env, *value, out, depth + 1);
+                }
+            }
+        },
+        Atom::HashMapValue(map) => {
+            out.extend(&[AV_ENC_MAP, map.table_len() as u64]);
+            for (key, value) in map.table_iter() {
+                out.push(key);
+                encode(env, value, out, depth + 1);
+            }
+        },
+        Atom::FunctionValue(_) => out.extend(&[AV_ENC_FUNCTION, symbol])
+    }
+}
+
+fn encode(env: &Environment, value: u64, out: &mut Vec<u64>, depth: usize) {
+    if depth > MAX_ENCODE_DEPTH {
+        out.extend(&[AV_ENC_ERROR, RUNTIME_ERR_CIRCULAR_DEP]);
+        return;
+    }
+    if is_error(value) {
+        out.extend(&[AV_ENC_ERROR, value]);
+    } else if is_number(value) || is_float_nan(value) {
+        out.extend(&[AV_ENC_NUMBER, value]);
+    } else if let Some(text) = as_text(env, value) {
+        encode_text(AV_ENC_TEXT, &text, out);
+    } else if let Some(items) = as_list(env, value) {
+        encode_atom(env, value, &Atom::ListValue(items), out, depth);
+    } else if is_pointer(value) {
+        match get_value(env, value) {
+            Ok(atom) => encode_atom(env, value, &atom, out, depth),
+            Err(err) => out.extend(&[AV_ENC_ERROR, err])
+        }
+    } else {
+        out.extend(&[AV_ENC_SYMBOL, value]);
+    }
+}
+
+// Encoded value, prefixed with the buffer length.
+pub fn encode_value(env: &Environment, value: u64) -> Vec<u64> {
+    let mut out: Vec<u64> = vec![0];
+    encode(env, value, &mut out, 0);
+    out[0] = out.len() as u64;
+    return out;
+}
+
+// Hand the buffer to the host. It must be released with __av_free_encoded.
+fn leak_buffer(buffer: Vec<u64>) -> *const u64 {
+    let leaked = Box::leak(buffer.into_boxed_slice());
+    return leaked.as_ptr();
+}
+
+#[no_mangle]
+pub extern "C" fn __av_get_encoded(env: &Environment, id: u64) -> *const u64 {
+    return leak_buffer(encode_value(env, id));
+}
+
+/// # Safety
+/// name_ptr must point to name_len readable bytes, which stay valid for the call.
+/// Invalid UTF-8 is reported as an unknown name.
+#[no_mangle]
+pub unsafe extern "C" fn __av_get_by_name_encoded(env: &Environment, name_ptr: *const u8, name_len: usize) -> *const u64 {
+    if name_ptr.is_null() {
+        return leak_buffer(encode_value(env, PARSE_ERR_UNK_SYMBOL));
+    }
+    let bytes = slice::from_raw_parts(name_ptr, name_len);
+    let symbol = match core::str::from_utf8(bytes).ok().and_then(|name| env.lookup_by_name(name.to_string())) {
+        Some(symbol) => *symbol,
+        None => PARSE_ERR_UNK_SYMBOL
+    };
+    return leak_buffer(encode_value(env, symbol));
+}
+
+/// # Safety
+/// ptr must be null or a buffer returned by __av_get_encoded or __av_get_by_name_encoded,
+/// and each buffer can only be freed once. Null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn __av_free_encoded(ptr: *const u64) {
+    if ptr.is_null() {
+        return;
+    }
+    let len = *ptr as usize;
+    let slice_ptr = slice::from_raw_parts_mut(ptr as *mut u64, len);
+    drop(Box::from_raw(slice_ptr));
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operators::__av_list;
+    use crate::decimal::Decimal;
+    use alloc::string::String;
+
+    fn decode_text(words: &[u64]) -> String {
+        let len = words[0] as usize;
+        let bytes: Vec<u8> = words[1..].iter()
+            .flat_map(|word| word.to_le_bytes().to_vec())
+            .take(len)
+            .collect();
+        return String::from_utf8(bytes).unwrap();
+    }
+
+    #[test]
+    fn test_get_value() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let total = env.define_identifier().unwrap();
+        env.bind_name(total, "Total".to_string()).unwrap();
+        env.bind_value(total, Atom::NumericValue(42.0));
+        let failed = env.define_identifier().unwrap();
+        env.bind_name(failed, "failed".to_string()).unwrap();
+        env.bind_result(failed, RUNTIME_ERR_DIV_Z);
+
+        assert!(get_value(&env, total) == Ok(Atom::NumericValue(42.0)));
+        assert!(get_value_by_name(&env, "TOTAL") == Ok(Atom::NumericValue(42.0)));
+        assert!(get_value_by_name(&env, "missing") == Err(PARSE_ERR_UNK_SYMBOL));
+        assert!(get_value(&env, total + 100) == Err(RUNTIME_ERR_UNK_VAL));
+        match get_value_by_name(&env, "failed") {
+            Err(err) => assert_eq!(error_code(err), RUNTIME_ERR_DIV_Z),
+            Ok(_) => panic!("Expected an error")
+        }
+        assert!(get_value(&env, SYMBOL_EMPTY_STR) == Ok(Atom::StringValue(String::new())));
+    }
+
+    #[test]
+    fn test_encode_value() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let text = env.init_value(Atom::StringValue("héllo world".to_string()));
+        let price = env.init_value(Atom::DecimalValue(Decimal::parse("0.10").unwrap()));
+        let list = __av_list(&mut env, vec![f64::to_bits(1.5), text, SYMBOL_TRUE.symbol, price]);
+
+        let encoded = encode_value(&env, list);
+        assert_eq!(encoded[0], encoded.len() as u64);
+        assert_eq!(&encoded[1..5], &[AV_ENC_LIST, 4, AV_ENC_NUMBER, f64::to_bits(1.5)]);
+        assert_eq!(encoded[5], AV_ENC_TEXT);
+        assert_eq!(decode_text(&encoded[6..9]), "héllo world");
+        assert_eq!(&encoded[9..11], &[AV_ENC_SYMBOL, SYMBOL_TRUE.symbol]);
+        assert_eq!(encoded[11], AV_ENC_DECIMAL);
+        assert_eq!(decode_text(&encoded[12..]), "0.10");
+
+        assert_eq!(encode_value(&env, RUNTIME_ERR_DIV_Z), vec![3, AV_ENC_ERROR, RUNTIME_ERR_DIV_Z]);
+        assert_eq!(encode_value(&env, AV_FN_MIN.symbol), vec![3, AV_ENC_FUNCTION, AV_FN_MIN.symbol]);
+    }
+
+    #[test]
+    fn test_encoded_by_name() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let total = env.define_identifier().unwrap();
+        env.bind_name(total, "total".to_string()).unwrap();
+        env.bind_value(total, Atom::NumericValue(7.0));
+
+        let name = "Total";
+        unsafe {
+            let ptr = __av_get_by_name_encoded(&env, name.as_ptr(), name.len());
+            let buffer = slice::from_raw_parts(ptr, 3).to_vec();
+            __av_free_encoded(ptr);
+            assert_eq!(buffer, vec![3, AV_ENC_NUMBER, f64::to_bits(7.0)]);
+
+            let ptr = __av_get_by_name_encoded(&env, "nope".as_ptr(), 4);
+            assert_eq!(*ptr.offset(2), PARSE_ERR_UNK_SYMBOL);
+            __av_free_encoded(ptr);
+
+            let ptr = __av_get_by_name_encoded(&env, core::ptr::null(), 0);
+            assert_eq!(*ptr.offset(2), PARSE_ERR_UNK_SYMBOL);
+            __av_free_encoded(ptr);
+            __av_free_encoded(core::ptr::null());
+        }
+    }
+}
@@ -10,10 +10,19 @@ pub mod structs;
 pub mod macros;
 pub mod utils;
 pub mod types;
+pub mod decimal;
+pub mod temporal;
+pub mod hashtable;
+pub mod record;
 pub mod operators;
+pub mod conditional;
 pub mod format;
 pub mod functions;
 pub mod expression;
+pub mod allocator;
+pub mod pmap;
+pub mod host;
+pub mod suggest;
 pub mod environment;
 
 #[cfg(not(target_os = "unknown"))]
@@ -63,12 +72,24 @@ pub extern "C" fn __av_save(env: &mut Environment, symbol: u64, value: Atom) {
 }
 
 
+// Stored value of a symbol, following pointers. None if it's unbound or part of a cycle.
+// Use __av_get_value to find out why a value couldn't be read.
 #[no_mangle]
 #[inline(never)]
 pub extern "C" fn __av_get(env: &mut Environment, id: u64) -> Option<&Atom> { 
-	// return env.get_atom(id);
-	// TODO 
-	return None
+	return env.deep_resolve(id).and_then(|ident| ident.value.as_ref());
+}
+
+// Resolved value of a symbol, or the error code explaining why it couldn't be read.
+// C hosts should use __av_get_encoded instead. See host.rs
+#[inline(never)]
+pub fn __av_get_value(env: &Environment, id: u64) -> Result<Atom, u64> { 
+	return host::get_value(env, id);
+}
+
+#[inline(never)]
+pub fn __av_get_value_by_name(env: &Environment, name: &str) -> Result<Atom, u64> { 
+	return host::get_value_by_name(env, name);
 }
 
 
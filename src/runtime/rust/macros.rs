@@ -22,6 +22,10 @@ macro_rules! disallow_nan {
 macro_rules! valid_num {
 	($val:expr) => ({
 		let f_val = f64::from_bits($val);
+		// Pass upstream errors through unchanged
+		if is_error($val) {
+			return $val
+		}
 		// Disallow nan or other data types
 		if is_nan(f_val) {
 			return RUNTIME_ERR_EXPECTED_NUM
@@ -30,6 +34,30 @@ macro_rules! valid_num {
 	})
 }
 
+#[macro_export]
+macro_rules! propagate_errors {
+	($($val:expr),+) => {
+		// Return the first error operand as is, so the root cause isn't hidden
+		$(
+			if is_error($val) {
+				return $val;
+			}
+		)+
+	}
+}
+
+#[macro_export]
+macro_rules! valid_int {
+	($val:expr) => ({
+		// Whole numbers only. For counts, calendar fields, etc.
+		let f_val: f64 = valid_num!($val);
+		if f_val.fract() != 0.0 {
+			return RUNTIME_ERR_EXPECTED_NUM
+		}
+		f_val as i64
+	})
+}
+
 #[macro_export]
 macro_rules! resolve_atom {
 	($env:expr, $val:expr) => ({
@@ -39,7 +67,6 @@ macro_rules! resolve_atom {
 			Atom::NumericValue(f_val)
 		} else if is_pointer($val) {
 			let symbol_resolution = $env.deep_resolve($val);
-			println!("Resolved symbol {:X} -> {:?}", $val, symbol_resolution);
 			if let Some(symbol_id) = symbol_resolution {
 				if let Some(symbol_value) = &symbol_id.value {
 					symbol_value.clone()
@@ -49,11 +76,12 @@ macro_rules! resolve_atom {
 			} else {
 				Atom::SymbolValue($val)
 			}
-		} else {
-			// println!("is symbol false");
-			// // Encountered other NaN Type
-			// return RUNTIME_ERR_EXPECTED_NUM
+		} else if is_float_nan($val) {
+			// A real NaN from a calculation, rather than a NaN-boxed value
 			Atom::NumericValue(f_val)
+		} else {
+			// Keywords and other inline symbols evaluate to themselves
+			Atom::SymbolValue($val)
 		}
 	})
 }
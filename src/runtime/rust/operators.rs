@@ -3,16 +3,545 @@ use crate::types::*;
 use crate::structs::*;
 use crate::constants::*;
 use crate::macros::*;
+use crate::decimal::*;
+use crate::temporal::*;
+use crate::utils::decode_small_string;
 use alloc::string::String;
 use alloc::borrow::Cow;
+use core::cmp::Ordering;
+
+
+// Floats are promoted to decimals when mixed with them, so the result stays exact.
+// Uses the shortest float representation. i.e. 0.1 is treated as exactly 0.1
+pub fn as_decimal(atom: &Atom) -> Option<Decimal> {
+	match atom {
+		Atom::DecimalValue(dec) => Some(*dec),
+		Atom::NumericValue(num) => Decimal::from_f64(*num),
+		_ => None
+	}
+}
+
+// Apply a decimal operation if either operand is a decimal. Results are stored in the environment.
+fn decimal_op(env: &mut Environment, atom_a: &Atom, atom_b: &Atom, op: fn(&Decimal, &Decimal) -> Result<Decimal, u64>) -> u64 {
+	match (atom_a, atom_b) {
+		(Atom::DecimalValue(_), _) | (_, Atom::DecimalValue(_)) => {
+			if let (Some(dec_a), Some(dec_b)) = (as_decimal(atom_a), as_decimal(atom_b)) {
+				match op(&dec_a, &dec_b) {
+					Ok(result) => return env.init_value(Atom::DecimalValue(result)),
+					Err(err) => return err
+				}
+			}
+			return RUNTIME_ERR_EXPECTED_NUM
+		},
+		_ => return RUNTIME_ERR_EXPECTED_NUM
+	}
+}
+
+fn decimal_add(a: &Decimal, b: &Decimal) -> Result<Decimal, u64> {
+	return a.checked_add(b).ok_or(RUNTIME_ERR_NUM_OVERFLOW);
+}
+
+fn decimal_sub(a: &Decimal, b: &Decimal) -> Result<Decimal, u64> {
+	return a.checked_sub(b).ok_or(RUNTIME_ERR_NUM_OVERFLOW);
+}
+
+fn decimal_mul(a: &Decimal, b: &Decimal) -> Result<Decimal, u64> {
+	return a.checked_mul(b).ok_or(RUNTIME_ERR_NUM_OVERFLOW);
+}
+
+fn decimal_mod(a: &Decimal, b: &Decimal) -> Result<Decimal, u64> {
+	if b.is_zero() {
+		return Err(RUNTIME_ERR_DIV_Z);
+	}
+	return a.checked_mod(b).ok_or(RUNTIME_ERR_NUM_OVERFLOW);
+}
+
+fn decimal_div(a: &Decimal, b: &Decimal) -> Result<Decimal, u64> {
+	if b.is_zero() {
+		return Err(RUNTIME_ERR_DIV_Z);
+	}
+	// Keep the precision of the inputs, but don't pad the result with extra zeroes.
+	let min_scale = a.scale.max(b.scale);
+	let places = DECIMAL_DIV_PLACES.max(min_scale);
+	return match a.checked_div(b, places, DECIMAL_DIV_ROUNDING) {
+		Some(result) => Ok(result.trim_zeros(min_scale)),
+		None => Err(RUNTIME_ERR_NUM_OVERFLOW)
+	};
+}
+
+
+fn is_temporal(atom: &Atom) -> bool {
+	matches!(atom, Atom::DateValue(_) | Atom::DateTimeValue(_) | Atom::DurationValue(_))
+}
+
+fn date_plus(date: &Date, duration: &Duration) -> Option<Atom> {
+	// Dates stay dates unless the duration includes a time of day
+	if duration.is_whole_days() {
+		return Some(Atom::DateValue(date.add_days(duration.days())?));
+	}
+	return Some(Atom::DateTimeValue(date.to_datetime()?.add(duration)?));
+}
+
+// Date arithmetic. date ± duration, datetime ± duration, date - date, duration ± duration
+// Returns None if neither operand is a date, time or duration.
+fn temporal_op(env: &mut Environment, atom_a: &Atom, atom_b: &Atom, subtract: bool) -> Option<u64> {
+	if !is_temporal(atom_a) && !is_temporal(atom_b) {
+		return None;
+	}
+
+	let result: Option<Atom> = match (atom_a, atom_b, subtract) {
+		(Atom::DateValue(date), Atom::DurationValue(duration), false) |
+		(Atom::DurationValue(duration), Atom::DateValue(date), false) => {
+			date_plus(date, duration)
+		},
+		(Atom::DateValue(date), Atom::DurationValue(duration), true) => {
+			duration.checked_neg().and_then(|negated| date_plus(date, &negated))
+		},
+		(Atom::DateTimeValue(datetime), Atom::DurationValue(duration), false) |
+		(Atom::DurationValue(duration), Atom::DateTimeValue(datetime), false) => {
+			datetime.add(duration).map(Atom::DateTimeValue)
+		},
+		(Atom::DateTimeValue(datetime), Atom::DurationValue(duration), true) => {
+			duration.checked_neg().and_then(|negated| datetime.add(&negated)).map(Atom::DateTimeValue)
+		},
+		(Atom::DurationValue(dur_a), Atom::DurationValue(dur_b), _) => {
+			let millis = if subtract { dur_a.millis.checked_sub(dur_b.millis) } else { dur_a.millis.checked_add(dur_b.millis) };
+			millis.map(|millis| Atom::DurationValue(Duration { millis: millis }))
+		},
+		(Atom::DateValue(date_a), Atom::DateValue(date_b), true) => {
+			date_a.days_since(date_b).and_then(Duration::from_days).map(Atom::DurationValue)
+		},
+		(Atom::DateTimeValue(dt_a), Atom::DateTimeValue(dt_b), true) => {
+			dt_a.since(dt_b).map(Atom::DurationValue)
+		},
+		(Atom::DateTimeValue(dt_a), Atom::DateValue(date_b), true) => {
+			date_b.to_datetime().and_then(|dt_b| dt_a.since(&dt_b)).map(Atom::DurationValue)
+		},
+		(Atom::DateValue(date_a), Atom::DateTimeValue(dt_b), true) => {
+			date_a.to_datetime().and_then(|dt_a| dt_a.since(dt_b)).map(Atom::DurationValue)
+		},
+		_ => return Some(RUNTIME_ERR_INVALID_TYPE)
+	};
+
+	match result {
+		Some(atom) => return Some(env.init_value(atom)),
+		None => return Some(RUNTIME_ERR_NUM_OVERFLOW)
+	}
+}
+
+// Ordering between comparable values. None if the values can't be ordered.
+pub fn compare_atoms(atom_a: &Atom, atom_b: &Atom) -> Option<Ordering> {
+	match (atom_a, atom_b) {
+		(Atom::NumericValue(f_a), Atom::NumericValue(f_b)) => f_a.partial_cmp(f_b),
+		(Atom::DecimalValue(_), Atom::DecimalValue(_)) |
+		(Atom::DecimalValue(_), Atom::NumericValue(_)) |
+		(Atom::NumericValue(_), Atom::DecimalValue(_)) => {
+			Some(as_decimal(atom_a)?.cmp(&as_decimal(atom_b)?))
+		},
+		(Atom::DateValue(date_a), Atom::DateValue(date_b)) => Some(date_a.cmp(date_b)),
+		(Atom::DateTimeValue(dt_a), Atom::DateTimeValue(dt_b)) => Some(dt_a.cmp(dt_b)),
+		(Atom::DateValue(date_a), Atom::DateTimeValue(dt_b)) => Some(date_a.to_datetime()?.cmp(dt_b)),
+		(Atom::DateTimeValue(dt_a), Atom::DateValue(date_b)) => Some(dt_a.cmp(&date_b.to_datetime()?)),
+		(Atom::DurationValue(dur_a), Atom::DurationValue(dur_b)) => Some(dur_a.cmp(dur_b)),
+		_ => None
+	}
+}
+
+// Lexicographic by unicode scalar value. UTF-8 byte order is the same as scalar order.
+// Case-insensitive collation folds with to_uppercase, the same as Environment names.
+pub fn compare_text(text_a: &str, text_b: &str, ignore_case: bool) -> Ordering {
+	if ignore_case {
+		return text_a.chars().flat_map(char::to_uppercase)
+			.cmp(text_b.chars().flat_map(char::to_uppercase));
+	}
+	return text_a.cmp(text_b);
+}
+
+// Slow path for comparisons of non-float values.
+fn compare_op(env: &mut Environment, a: u64, b: u64, test: fn(Ordering) -> bool) -> u64 {
+	propagate_errors!(a, b);
+	if let (Some(text_a), Some(text_b)) = (as_text(env, a), as_text(env, b)) {
+		return __repr_bool(test(compare_text(&text_a, &text_b, false)));
+	}
+	let atom_a = resolve_atom!(env, a);
+	let atom_b = resolve_atom!(env, b);
+	match compare_atoms(&atom_a, &atom_b) {
+		Some(ordering) => return __repr_bool(test(ordering)),
+		None => return RUNTIME_ERR_EXPECTED_NUM
+	}
+}
+
+
+// Stored value of a pointer, borrowed rather than cloned like resolve_atom!.
+// None for inline values, errors and unbound symbols.
+pub fn resolve_ref(env: &Environment, value: u64) -> Option<&Atom> {
+	if !is_pointer(value) || is_error(value) {
+		return None;
+	}
+	return env.deep_resolve(value).and_then(|ident| ident.value.as_ref());
+}
+
+// Elements of a list value. The empty list symbol is a list with no elements.
+pub fn as_list(env: &Environment, value: u64) -> Option<Vec<u64>> {
+	if value == SYMBOL_EMPTY_ARR {
+		return Some(Vec::new());
+	}
+	if is_pointer(value) {
+		if let Some(ident) = env.deep_resolve(value) {
+			if let Some(Atom::ListValue(items)) = &ident.value {
+				return Some(items.clone());
+			}
+		}
+	}
+	return None;
+}
+
+// Resolve a zero-based or negative (from the end) index into a position.
+pub fn list_position(index: i64, len: usize) -> Option<usize> {
+	let position = if index < 0 { index + len as i64 } else { index };
+	if position < 0 || position >= len as i64 {
+		return None;
+	}
+	return Some(position as usize);
+}
+
+// Text content of heap, inline or empty strings
+pub fn as_text(env: &Environment, value: u64) -> Option<String> {
+	if !is_string(value) && !is_pointer(value) {
+		return None;
+	}
+	if let Some(text) = decode_small_string(value) {
+		return Some(text);
+	}
+	if is_pointer(value) && !is_error(value) {
+		if let Some(ident) = env.deep_resolve(value) {
+			if let Some(Atom::StringValue(text)) = &ident.value {
+				return Some(text.clone());
+			}
+		}
+	}
+	return None;
+}
+
+// Structural equality of two raw values. Never errors. Values of different types are unequal.
+// Numbers follow IEEE 754. 0 == -0, and NaN is not equal to anything, including itself.
+// Numbers and decimals are compared by value, so 0.1 == decimal("0.1").
+pub fn values_equal(env: &Environment, a: u64, b: u64) -> bool {
+	if (is_number(a) || is_float_nan(a)) && (is_number(b) || is_float_nan(b)) {
+		return f64::from_bits(a) == f64::from_bits(b);
+	}
+	if a == b {
+		return true;
+	}
+	if let (Some(text_a), Some(text_b)) = (as_text(env, a), as_text(env, b)) {
+		return text_a == text_b;
+	}
+	if let (Some(list_a), Some(list_b)) = (as_list(env, a), as_list(env, b)) {
+		return list_a.len() == list_b.len() &&
+			list_a.iter().zip(list_b.iter()).all(|(x, y)| values_equal(env, *x, *y));
+	}
+	if !is_pointer(a) && !is_pointer(b) {
+		// Distinct keywords, bools, None or inline values
+		return false;
+	}
+	if is_error(a) || is_error(b) {
+		return false;
+	}
+	// Maps and objects are compared in place
+	if let (Some(atom_a), Some(atom_b)) = (resolve_ref(env, a), resolve_ref(env, b)) {
+		return atoms_equal(env, atom_a, atom_b);
+	}
+	let atom_a = resolve_atom!(env, a);
+	let atom_b = resolve_atom!(env, b);
+	return atoms_equal(env, &atom_a, &atom_b);
+}
+
+// Same keys, with equal values
+fn tables_equal(env: &Environment, table_a: &AvObject, table_b: &AvObject) -> bool {
+	return table_a.table_len() == table_b.table_len() && table_a.table_iter().all(|(key, value_a)| {
+		match table_b.table_get(key) {
+			Some(value_b) => values_equal(env, value_a, value_b),
+			None => false
+		}
+	});
+}
+
+pub fn atoms_equal(env: &Environment, atom_a: &Atom, atom_b: &Atom) -> bool {
+	match (atom_a, atom_b) {
+		(Atom::NumericValue(f_a), Atom::NumericValue(f_b)) => f_a == f_b,
+		(Atom::DecimalValue(_), Atom::DecimalValue(_)) |
+		(Atom::DecimalValue(_), Atom::NumericValue(_)) |
+		(Atom::NumericValue(_), Atom::DecimalValue(_)) => {
+			match (as_decimal(atom_a), as_decimal(atom_b)) {
+				(Some(dec_a), Some(dec_b)) => dec_a == dec_b,
+				_ => false
+			}
+		},
+		(Atom::StringValue(str_a), Atom::StringValue(str_b)) => str_a == str_b,
+		// resolve_atom! already followed every pointer it could, so these are
+		// keywords, unbound names or cycles. Recursing would never terminate.
+		(Atom::SymbolValue(sym_a), Atom::SymbolValue(sym_b)) => sym_a == sym_b,
+		(Atom::ListValue(items_a), Atom::ListValue(items_b)) => {
+			items_a.len() == items_b.len() &&
+				items_a.iter().zip(items_b.iter()).all(|(x, y)| values_equal(env, *x, *y))
+		},
+		(Atom::HashMapValue(map_a), Atom::HashMapValue(map_b)) => tables_equal(env, map_a, map_b),
+		(Atom::ObjectValue(obj_a), Atom::ObjectValue(obj_b)) => {
+			// Compared by class and field values, not by identity
+			if obj_a.av_class != obj_b.av_class {
+				return false;
+			}
+			if obj_a.is_table() && obj_b.is_table() {
+				return tables_equal(env, obj_a, obj_b);
+			}
+			return obj_a.av_values == obj_b.av_values;
+		},
+		(Atom::FunctionValue(fn_a), Atom::FunctionValue(fn_b)) => fn_a == fn_b,
+		_ => {
+			match compare_atoms(atom_a, atom_b) {
+				// Dates, times and durations
+				Some(ordering) => ordering == Ordering::Equal,
+				None => false
+			}
+		}
+	}
+}
+
+// Position of each kind of value in the total order.
+// numbers < text < dates < durations < bools < lists < other values < None < errors
+fn order_rank(value_type: &ValueType) -> u8 {
+	match value_type {
+		ValueType::NumericType | ValueType::DecimalType => 0,
+		ValueType::StringType => 1,
+		ValueType::DateType | ValueType::DateTimeType => 2,
+		ValueType::DurationType => 3,
+		ValueType::BoolType => 4,
+		ValueType::ListType => 5,
+		ValueType::HashMapType | ValueType::ObjectType |
+		ValueType::FunctionType | ValueType::SymbolType => 6,
+		ValueType::NoneType => 7,
+		ValueType::ErrorType => 8
+	}
+}
+
+// Total order over all values, used for sorting. Never errors.
+// Values of the same type use their natural order. NaN sorts after all other numbers.
+// Values without a natural order (objects, functions) fall back to their symbol.
+pub fn total_order(env: &Environment, a: u64, b: u64, ignore_case: bool) -> Ordering {
+	let type_a = __av_type(env, a);
+	let type_b = __av_type(env, b);
+	let rank_a = order_rank(&type_a);
+	let rank_b = order_rank(&type_b);
+	if rank_a != rank_b {
+		return rank_a.cmp(&rank_b);
+	}
+
+	match rank_a {
+		0 => {
+			// Numbers may be stored in cells, so compare what they point to
+			let atom_a = resolve_atom!(env, a);
+			let atom_b = resolve_atom!(env, b);
+			let nan_a = matches!(atom_a, Atom::NumericValue(num) if num.is_nan());
+			let nan_b = matches!(atom_b, Atom::NumericValue(num) if num.is_nan());
+			// NaNs are equal to each other and greater than every number or decimal
+			match (nan_a, nan_b) {
+				(true, true) => return Ordering::Equal,
+				(true, false) => return Ordering::Greater,
+				(false, true) => return Ordering::Less,
+				(false, false) => {}
+			}
+			if let Some(ordering) = compare_atoms(&atom_a, &atom_b) {
+				return ordering;
+			}
+		},
+		1 => {
+			if let (Some(text_a), Some(text_b)) = (as_text(env, a), as_text(env, b)) {
+				return compare_text(&text_a, &text_b, ignore_case);
+			}
+		},
+		4 => return __av_truthy(env, a).cmp(&__av_truthy(env, b)),
+		5 => {
+			if let (Some(list_a), Some(list_b)) = (as_list(env, a), as_list(env, b)) {
+				for (x, y) in list_a.iter().zip(list_b.iter()) {
+					let ordering = total_order(env, *x, *y, ignore_case);
+					if ordering != Ordering::Equal {
+						return ordering;
+					}
+				}
+				return list_a.len().cmp(&list_b.len());
+			}
+		},
+		6..=8 => return a.cmp(&b),
+		_ => {}
+	}
+
+	let atom_a = resolve_atom!(env, a);
+	let atom_b = resolve_atom!(env, b);
+	if let Some(ordering) = compare_atoms(&atom_a, &atom_b) {
+		return ordering;
+	}
+	return a.cmp(&b);
+}
+
+#[no_mangle]
+pub extern fn __av_eq(env: &mut Environment, a: u64, b: u64) -> u64 {
+	propagate_errors!(a, b);
+	return __repr_bool(values_equal(env, a, b));
+}
+
+#[no_mangle]
+pub extern fn __av_ne(env: &mut Environment, a: u64, b: u64) -> u64 {
+	propagate_errors!(a, b);
+	return __repr_bool(!values_equal(env, a, b));
+}
+
+// Target of list literals. [a, b, c]
+pub fn __av_list(env: &mut Environment, items: Vec<u64>) -> u64 {
+	if items.is_empty() {
+		return SYMBOL_EMPTY_ARR;
+	}
+	return env.init_value(Atom::ListValue(items));
+}
+
+// Build a list literal from its tokens, with the elements already evaluated.
+// [1, [2, 3], x] arrives as [ 1 , [ 2 , 3 ] , x ]
+// Returns the list and the number of tokens it used, so the caller can continue after it.
+pub fn __av_list_literal(env: &mut Environment, tokens: &[u64]) -> Result<(u64, usize), u64> {
+	if tokens.first() != Some(&SYMBOL_OPEN_SQBR.symbol) {
+		return Err(PARSE_ERR_UNEXPECTED_TOKEN);
+	}
+	let mut items: Vec<u64> = Vec::new();
+	let mut pos = 1;
+	if tokens.get(pos) == Some(&SYMBOL_CLOSE_SQBR.symbol) {
+		return Ok((SYMBOL_EMPTY_ARR, pos + 1));
+	}
+	loop {
+		// Element
+		match tokens.get(pos) {
+			None => return Err(PARSE_ERR_UNMATCHED_PARENS),
+			Some(token) if *token == SYMBOL_OPEN_SQBR.symbol => {
+				let (nested, used) = __av_list_literal(env, &tokens[pos..])?;
+				items.push(nested);
+				pos += used;
+			},
+			Some(token) if *token == SYMBOL_COMMA.symbol || *token == SYMBOL_CLOSE_SQBR.symbol => {
+				return Err(PARSE_ERR_UNEXPECTED_TOKEN);
+			},
+			Some(token) => {
+				items.push(*token);
+				pos += 1;
+			}
+		}
+		// Separator
+		match tokens.get(pos) {
+			None => return Err(PARSE_ERR_UNMATCHED_PARENS),
+			Some(token) if *token == SYMBOL_COMMA.symbol => pos += 1,
+			Some(token) if *token == SYMBOL_CLOSE_SQBR.symbol => {
+				return Ok((__av_list(env, items), pos + 1));
+			},
+			Some(_) => return Err(PARSE_ERR_UNEXPECTED_TOKEN)
+		}
+	}
+}
+
+#[no_mangle]
+pub extern fn __av_index(env: &mut Environment, list: u64, index: u64) -> u64 {
+	propagate_errors!(list, index);
+	let f_index: f64 = valid_num!(index);
+	if f_index.fract() != 0.0 {
+		return RUNTIME_ERR_EXPECTED_NUM;
+	}
+	match as_list(env, list) {
+		Some(items) => {
+			match list_position(f_index as i64, items.len()) {
+				Some(position) => return items[position],
+				None => return RUNTIME_ERR_INDEX_BOUNDS
+			}
+		},
+		None => return RUNTIME_ERR_EXPECTED_LIST
+	}
+}
+
+
+// Convert a resolved Atom back into a raw value, storing heap values in the environment.
+pub fn store_atom(env: &mut Environment, atom: Atom) -> u64 {
+	match atom {
+		Atom::NumericValue(num) => return num.to_bits(),
+		Atom::SymbolValue(symbol) => return symbol,
+		_ => return env.init_value(atom)
+	}
+}
+
+fn date_property(date: &Date, field: u64) -> u64 {
+	let (year, month, day) = date.ymd();
+	if field == AV_PROP_YEAR.symbol {
+		return (year as f64).to_bits();
+	} else if field == AV_PROP_MONTH.symbol {
+		return (month as f64).to_bits();
+	} else if field == AV_PROP_DAY.symbol {
+		return (day as f64).to_bits();
+	}
+	return RUNTIME_ERR_UNK_FIELD
+}
+
+// Member access. a.b
+// Objects look up fields by symbol, maps by key. Other values have built in properties.
+#[no_mangle]
+pub extern fn __av_dot(env: &mut Environment, a: u64, field: u64) -> u64 {
+	propagate_errors!(a);
+	if let Some(items) = as_list(env, a) {
+		if field == AV_PROP_LENGTH.symbol {
+			return (items.len() as f64).to_bits();
+		}
+		return RUNTIME_ERR_UNK_FIELD;
+	}
+
+	// Short and empty strings are stored inline rather than behind a pointer
+	if let Some(text) = as_text(env, a) {
+		if field == AV_PROP_LENGTH.symbol {
+			return (text.chars().count() as f64).to_bits();
+		}
+		return RUNTIME_ERR_UNK_FIELD;
+	}
+
+	// Read in place, so large maps aren't copied for each field access
+	match resolve_ref(env, a) {
+		// Fields belong to instances. The class only holds their defaults.
+		Some(Atom::ObjectValue(obj)) if obj.av_class == AV_CLASS_CLASS => return RUNTIME_ERR_INVALID_TYPE,
+		Some(Atom::ObjectValue(obj)) | Some(Atom::HashMapValue(obj)) => {
+			match obj.table_get(field) {
+				Some(value) => return value,
+				None => return RUNTIME_ERR_UNK_FIELD
+			}
+		},
+		Some(Atom::DateValue(date)) => return date_property(date, field),
+		Some(Atom::DateTimeValue(datetime)) => return date_property(&datetime.date(), field),
+		_ => return RUNTIME_ERR_INVALID_TYPE
+	}
+}
 
 
 #[no_mangle]
 pub extern fn __av_add(env: &mut Environment, a: u64, b: u64) -> u64 {
 	// + is an overloaded operator, allowing combinations across various things
 	// To prevent exponential branching, resolve both elements to Atoms and then do the math.
+	if !is_number(a) || !is_number(b) {
+		propagate_errors!(a, b);
+	}
+	if !is_number(a) && !is_number(b) {
+		if let (Some(mut list_a), Some(mut list_b)) = (as_list(env, a), as_list(env, b)) {
+			list_a.append(&mut list_b);
+			return __av_list(env, list_a);
+		}
+	}
 	let atom_a = resolve_atom!(env, a);
 	let atom_b = resolve_atom!(env, b);
+	if let (Atom::DecimalValue(_), _) | (_, Atom::DecimalValue(_)) = (&atom_a, &atom_b) {
+		return decimal_op(env, &atom_a, &atom_b, decimal_add);
+	}
+	if let Some(result) = temporal_op(env, &atom_a, &atom_b, false) {
+		return result;
+	}
 	match atom_a {
 		Atom::NumericValue(f_a) => {
 			match atom_b {
@@ -41,21 +570,42 @@ pub extern fn __av_add(env: &mut Environment, a: u64, b: u64) -> u64 {
 }
 
 #[no_mangle]
-pub extern fn __av_sub(_env: &mut Environment, a: u64, b: u64) -> u64 {
+pub extern fn __av_sub(env: &mut Environment, a: u64, b: u64) -> u64 {
+	if !is_number(a) || !is_number(b) {
+		propagate_errors!(a, b);
+		let atom_a = resolve_atom!(env, a);
+		let atom_b = resolve_atom!(env, b);
+		if let Some(result) = temporal_op(env, &atom_a, &atom_b, true) {
+			return result;
+		}
+		return decimal_op(env, &atom_a, &atom_b, decimal_sub);
+	}
 	let f_a: f64 = valid_num!(a);
 	let f_b: f64 = valid_num!(b);
 	return (f_a - f_b).to_bits()
 }
 
 #[no_mangle]
-pub extern fn __av_mul(_env: &mut Environment, a: u64, b: u64) -> u64 {
+pub extern fn __av_mul(env: &mut Environment, a: u64, b: u64) -> u64 {
+	if !is_number(a) || !is_number(b) {
+		propagate_errors!(a, b);
+		let atom_a = resolve_atom!(env, a);
+		let atom_b = resolve_atom!(env, b);
+		return decimal_op(env, &atom_a, &atom_b, decimal_mul);
+	}
 	let f_a: f64 = valid_num!(a);
 	let f_b: f64 = valid_num!(b);
 	return (f_a * f_b).to_bits()
 }
 
 #[no_mangle]
-pub extern fn __av_div(_env: &mut Environment, a: u64, b: u64) -> u64 {
+pub extern fn __av_div(env: &mut Environment, a: u64, b: u64) -> u64 {
+	if !is_number(a) || !is_number(b) {
+		propagate_errors!(a, b);
+		let atom_a = resolve_atom!(env, a);
+		let atom_b = resolve_atom!(env, b);
+		return decimal_op(env, &atom_a, &atom_b, decimal_div);
+	}
 	let f_a: f64 = valid_num!(a);
 	let f_b: f64 = valid_num!(b);
 
@@ -67,31 +617,177 @@ pub extern fn __av_div(_env: &mut Environment, a: u64, b: u64) -> u64 {
 }
 
 
+// Floored modulo. The result takes the sign of the divisor, so -7 % 2 = 1
 #[no_mangle]
-pub extern fn __av_and(_env: &mut Environment, a: u64, b: u64) -> u64 {
-	let a_bool: bool = __av_as_bool(a);
-	let b_bool: bool = __av_as_bool(b);
-	let result: bool = a_bool && b_bool;
-	return __repr_bool(result);
+pub extern fn __av_mod(env: &mut Environment, a: u64, b: u64) -> u64 {
+	if !is_number(a) || !is_number(b) {
+		propagate_errors!(a, b);
+		let atom_a = resolve_atom!(env, a);
+		let atom_b = resolve_atom!(env, b);
+		return decimal_op(env, &atom_a, &atom_b, decimal_mod);
+	}
+	let f_a: f64 = valid_num!(a);
+	let f_b: f64 = valid_num!(b);
+
+	if f_b == 0.0 {
+		return RUNTIME_ERR_DIV_Z;
+	}
+
+	return (f_a - f_b * (f_a / f_b).floor()).to_bits()
 }
 
+// Exponentiation. a ^ b
+// Decimals with whole number exponents stay exact.
 #[no_mangle]
-pub extern fn __av_or(_env: &mut Environment, a: u64, b: u64) -> u64 {
-	let a_bool: bool = __av_as_bool(a);
-	let b_bool: bool = __av_as_bool(b);
-	let result: bool = a_bool || b_bool;
-	return __repr_bool(result);
+pub extern fn __av_pow(env: &mut Environment, a: u64, b: u64) -> u64 {
+	if !is_number(a) || !is_number(b) {
+		propagate_errors!(a, b);
+		let atom_a = resolve_atom!(env, a);
+		let atom_b = resolve_atom!(env, b);
+		if let (Atom::DecimalValue(_), _) | (_, Atom::DecimalValue(_)) = (&atom_a, &atom_b) {
+			let (dec_a, f_b) = match (as_decimal(&atom_a), as_decimal(&atom_b)) {
+				(Some(dec_a), Some(dec_b)) => (dec_a, dec_b.to_f64()),
+				_ => return RUNTIME_ERR_EXPECTED_NUM
+			};
+			if f_b.fract() == 0.0 && f_b.abs() <= i32::MAX as f64 {
+				if dec_a.is_zero() && f_b < 0.0 {
+					return RUNTIME_ERR_DIV_Z;
+				}
+				match dec_a.checked_powi(f_b as i64) {
+					Some(result) => return env.init_value(Atom::DecimalValue(result)),
+					None => return RUNTIME_ERR_NUM_OVERFLOW
+				}
+			}
+			// Fractional powers are inexact anyways
+			return __av_pow(env, dec_a.to_f64().to_bits(), f_b.to_bits());
+		}
+	}
+	let f_a: f64 = valid_num!(a);
+	let f_b: f64 = valid_num!(b);
+
+	if f_a == 0.0 && f_b < 0.0 {
+		return RUNTIME_ERR_DIV_Z;
+	}
+
+	let result = f_a.powf(f_b);
+	if is_nan(result) {
+		// i.e. Fractional power of a negative number
+		return RUNTIME_ERR_TYPE_NAN;
+	}
+	return result.to_bits()
 }
 
+// Unary minus. -a
 #[no_mangle]
-pub extern fn __av_not(_env: &mut Environment, a: u64) -> u64 {
-	let a_bool: bool = __av_as_bool(a);
+pub extern fn __av_neg(env: &mut Environment, a: u64) -> u64 {
+	if !is_number(a) {
+		propagate_errors!(a);
+		if let Atom::DecimalValue(dec_a) = resolve_atom!(env, a) {
+			return env.init_value(Atom::DecimalValue(dec_a.neg()));
+		}
+	}
+	let f_a: f64 = valid_num!(a);
+	return (-f_a).to_bits()
+}
+
+
+// Left operand of `and`. False or an error decides the result without the right operand.
+pub fn __av_and_left(env: &Environment, a: u64) -> Option<u64> {
+	if is_error(a) {
+		return Some(a);
+	}
+	if !__av_truthy(env, a) {
+		return Some(SYMBOL_FALSE.symbol);
+	}
+	return None;
+}
+
+// Left operand of `or`. True or an error decides the result without the right operand.
+pub fn __av_or_left(env: &Environment, a: u64) -> Option<u64> {
+	if is_error(a) {
+		return Some(a);
+	}
+	if __av_truthy(env, a) {
+		return Some(SYMBOL_TRUE.symbol);
+	}
+	return None;
+}
+
+// Eager form, for when both operands are already evaluated. Errors propagate rather than being false.
+#[no_mangle]
+pub extern fn __av_and(env: &mut Environment, a: u64, b: u64) -> u64 {
+	if let Some(result) = __av_and_left(env, a) {
+		return result;
+	}
+	if is_error(b) {
+		return b;
+	}
+	return __repr_bool(__av_truthy(env, b));
+}
+
+#[no_mangle]
+pub extern fn __av_or(env: &mut Environment, a: u64, b: u64) -> u64 {
+	if let Some(result) = __av_or_left(env, a) {
+		return result;
+	}
+	if is_error(b) {
+		return b;
+	}
+	return __repr_bool(__av_truthy(env, b));
+}
+
+// Left operand of ??. Anything other than None, including errors, is the result.
+// Names bound to None count as None.
+pub fn __av_coalesce_left(env: &Environment, a: u64) -> Option<u64> {
+	if a == SYMBOL_NONE.symbol {
+		return None;
+	}
+	if is_pointer(a) && !is_error(a) {
+		if let Some(Atom::SymbolValue(symbol)) = env.deep_resolve(a).and_then(|ident| ident.value.as_ref()) {
+			if *symbol == SYMBOL_NONE.symbol {
+				return None;
+			}
+		}
+	}
+	return Some(a);
+}
+
+#[no_mangle]
+pub extern fn __av_coalesce(env: &mut Environment, a: u64, b: u64) -> u64 {
+	if let Some(result) = __av_coalesce_left(env, a) {
+		return result;
+	}
+	return b;
+}
+
+// Apply a binary keyword, evaluating the right operand only when it's needed.
+// Entry point for evaluators. x != 0 and 10 / x > 1 never divides by zero.
+pub fn apply_lazy(env: &mut Environment, keyword: &Keyword, a: u64, right: &mut dyn FnMut(&mut Environment) -> u64) -> u64 {
+	if let Some(short_circuit) = keyword.short_circuit {
+		if let Some(result) = short_circuit(env, a) {
+			return result;
+		}
+	}
+	let b = right(env);
+	match keyword.operation {
+		Some(operation) => return operation(env, a, b),
+		None => return RUNTIME_ERR_INVALID_TYPE
+	}
+}
+
+#[no_mangle]
+pub extern fn __av_not(env: &mut Environment, a: u64) -> u64 {
+	propagate_errors!(a);
+	let a_bool: bool = __av_truthy(env, a);
 	let result: bool = !a_bool;
 	return __repr_bool(result);
 }
 
 #[no_mangle]
-pub extern fn __av_gt(_env: &mut Environment, a: u64, b: u64) -> u64 {
+pub extern fn __av_gt(env: &mut Environment, a: u64, b: u64) -> u64 {
+	if !is_number(a) || !is_number(b) {
+		return compare_op(env, a, b, |ord| ord == Ordering::Greater);
+	}
 	let f_a: f64 = valid_num!(a);
 	let f_b: f64 = valid_num!(b);
 	let result = f_a > f_b;
@@ -99,7 +795,10 @@ pub extern fn __av_gt(_env: &mut Environment, a: u64, b: u64) -> u64 {
 }
 
 #[no_mangle]
-pub extern fn __av_gte(_env: &mut Environment, a: u64, b: u64) -> u64 {
+pub extern fn __av_gte(env: &mut Environment, a: u64, b: u64) -> u64 {
+	if !is_number(a) || !is_number(b) {
+		return compare_op(env, a, b, |ord| ord != Ordering::Less);
+	}
 	let f_a: f64 = valid_num!(a);
 	let f_b: f64 = valid_num!(b);
 	let result = f_a >= f_b;
@@ -108,7 +807,10 @@ pub extern fn __av_gte(_env: &mut Environment, a: u64, b: u64) -> u64 {
 
 
 #[no_mangle]
-pub extern fn __av_lt(_env: &mut Environment, a: u64, b: u64) -> u64 {
+pub extern fn __av_lt(env: &mut Environment, a: u64, b: u64) -> u64 {
+	if !is_number(a) || !is_number(b) {
+		return compare_op(env, a, b, |ord| ord == Ordering::Less);
+	}
 	let f_a: f64 = valid_num!(a);
 	let f_b: f64 = valid_num!(b);
 	let result = f_a < f_b;
@@ -116,9 +818,434 @@ pub extern fn __av_lt(_env: &mut Environment, a: u64, b: u64) -> u64 {
 }
 
 #[no_mangle]
-pub extern fn __av_lte(_env: &mut Environment, a: u64, b: u64) -> u64 {
+pub extern fn __av_lte(env: &mut Environment, a: u64, b: u64) -> u64 {
+	if !is_number(a) || !is_number(b) {
+		return compare_op(env, a, b, |ord| ord != Ordering::Greater);
+	}
 	let f_a: f64 = valid_num!(a);
 	let f_b: f64 = valid_num!(b);
 	let result = f_a <= f_b;
 	return __repr_bool(result);
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_decimal_ops() {
+		let mut env = Environment::new(APP_SYMBOL_START);
+		let tenth = env.init_value(Atom::DecimalValue(Decimal::parse("0.1").unwrap()));
+		let fifth = env.init_value(Atom::DecimalValue(Decimal::parse("0.2").unwrap()));
+
+		let sum = __av_add(&mut env, tenth, fifth);
+		assert_eq!(resolve_atom!(env, sum), Atom::DecimalValue(Decimal::parse("0.3").unwrap()));
+
+		// Mixed with floats
+		let mixed = __av_mul(&mut env, tenth, f64::to_bits(3.0));
+		assert_eq!(resolve_atom!(env, mixed), Atom::DecimalValue(Decimal::parse("0.3").unwrap()));
+
+		let quotient = __av_div(&mut env, f64::to_bits(1.0), fifth);
+		assert_eq!(resolve_atom!(env, quotient), Atom::DecimalValue(Decimal::parse("5").unwrap()));
+
+		let zero = env.init_value(Atom::DecimalValue(Decimal::parse("0.00").unwrap()));
+		assert_eq!(__av_div(&mut env, tenth, zero), RUNTIME_ERR_DIV_Z);
+
+		// Explicit places and rounding
+		use crate::functions::__av_div_round;
+		let half_up = env.init_value(Atom::StringValue("half_up".to_string()));
+		let down = env.init_value(Atom::StringValue("down".to_string()));
+		let rounded = __av_div_round(&mut env, f64::to_bits(2.0), f64::to_bits(3.0), f64::to_bits(2.0), half_up);
+		assert_eq!(resolve_atom!(env, rounded), Atom::DecimalValue(Decimal::parse("0.67").unwrap()));
+		let truncated = __av_div_round(&mut env, f64::to_bits(2.0), f64::to_bits(3.0), f64::to_bits(2.0), down);
+		assert_eq!(resolve_atom!(env, truncated), Atom::DecimalValue(Decimal::parse("0.66").unwrap()));
+		assert_eq!(__av_div_round(&mut env, tenth, zero, f64::to_bits(2.0), down), RUNTIME_ERR_DIV_Z);
+		assert_eq!(__av_div_round(&mut env, tenth, fifth, f64::to_bits(0.5), down), RUNTIME_ERR_INVALID_TYPE);
+		assert_eq!(__av_div_round(&mut env, tenth, fifth, f64::to_bits(2.0), tenth), RUNTIME_ERR_EXPECTED_STR);
+
+		// Numeric builtins accept decimals
+		use crate::functions::{__av_min, __av_max, __av_sqrt};
+		assert_eq!(__av_min(&mut env, tenth, fifth), tenth);
+		assert_eq!(__av_max(&mut env, tenth, fifth), fifth);
+		assert_eq!(__av_min(&mut env, fifth, f64::to_bits(0.15)), f64::to_bits(0.15));
+		assert_eq!(__av_max(&mut env, f64::to_bits(0.15), fifth), fifth);
+		assert_eq!(__av_min(&mut env, f64::to_bits(2.0), f64::to_bits(-1.0)), f64::to_bits(-1.0));
+		assert_eq!(__av_min(&mut env, tenth, f64::NAN.to_bits()), RUNTIME_ERR_EXPECTED_NUM);
+		let four = env.init_value(Atom::DecimalValue(Decimal::parse("4.00").unwrap()));
+		assert_eq!(__av_sqrt(&mut env, four), f64::to_bits(2.0));
+	}
+
+	#[test]
+	fn test_date_ops() {
+		let mut env = Environment::new(APP_SYMBOL_START);
+		let start = env.init_value(Atom::DateValue(Date::from_ymd(2020, 2, 27).unwrap()));
+		let end = env.init_value(Atom::DateValue(Date::from_ymd(2020, 3, 2).unwrap()));
+		let week = env.init_value(Atom::DurationValue(Duration::from_days(7).unwrap()));
+
+		let later = __av_add(&mut env, start, week);
+		assert_eq!(resolve_atom!(env, later), Atom::DateValue(Date::from_ymd(2020, 3, 5).unwrap()));
+
+		let elapsed = __av_sub(&mut env, end, start);
+		assert_eq!(resolve_atom!(env, elapsed), Atom::DurationValue(Duration::from_days(4).unwrap()));
+
+		assert_eq!(__av_lt(&mut env, start, end), SYMBOL_TRUE.symbol);
+		assert_eq!(__av_gte(&mut env, start, end), SYMBOL_FALSE.symbol);
+		assert_eq!(__av_mul(&mut env, start, end), RUNTIME_ERR_EXPECTED_NUM);
+		assert_eq!(__av_add(&mut env, start, end), RUNTIME_ERR_INVALID_TYPE);
+
+		// Out of range dates are errors rather than overflows
+		use crate::functions::{__av_date, __av_add_days, __av_diff};
+		assert_eq!(__av_date(&mut env, f64::to_bits(1e18), f64::to_bits(1.0), f64::to_bits(1.0)), RUNTIME_ERR_INVALID_DATE);
+		assert_eq!(__av_add_days(&mut env, start, f64::to_bits(1e15)), RUNTIME_ERR_NUM_OVERFLOW);
+		let far = __av_add_days(&mut env, start, f64::to_bits(9e7));
+		assert!(!is_error(__av_diff(&mut env, far, start)));
+		let min = env.init_value(Atom::DateValue(Date { days: i64::MIN }));
+		let max = env.init_value(Atom::DateValue(Date { days: i64::MAX }));
+		assert_eq!(__av_sub(&mut env, max, min), RUNTIME_ERR_NUM_OVERFLOW);
+		assert_eq!(__av_diff(&mut env, start, max), RUNTIME_ERR_NUM_OVERFLOW);
+		let most_negative = env.init_value(Atom::DurationValue(Duration { millis: i64::MIN }));
+		assert_eq!(__av_sub(&mut env, start, most_negative), RUNTIME_ERR_NUM_OVERFLOW);
+		let datetime = env.init_value(Atom::DateTimeValue(DateTime { millis: 0 }));
+		assert_eq!(__av_sub(&mut env, datetime, most_negative), RUNTIME_ERR_NUM_OVERFLOW);
+	}
+
+	#[test]
+	fn test_list_ops() {
+		let mut env = Environment::new(APP_SYMBOL_START);
+		let text = env.init_value(Atom::StringValue("row".to_string()));
+		let list = __av_list(&mut env, vec![f64::to_bits(1.0), f64::to_bits(2.0), text]);
+
+		assert_eq!(__av_index(&mut env, list, f64::to_bits(0.0)), f64::to_bits(1.0));
+		assert_eq!(__av_index(&mut env, list, f64::to_bits(-1.0)), text);
+		assert_eq!(__av_index(&mut env, list, f64::to_bits(3.0)), RUNTIME_ERR_INDEX_BOUNDS);
+		assert_eq!(__av_index(&mut env, list, f64::to_bits(-4.0)), RUNTIME_ERR_INDEX_BOUNDS);
+		assert_eq!(__av_index(&mut env, SYMBOL_EMPTY_ARR, f64::to_bits(0.0)), RUNTIME_ERR_INDEX_BOUNDS);
+
+		let other = __av_list(&mut env, vec![f64::to_bits(3.0)]);
+		let joined = __av_add(&mut env, list, other);
+		assert_eq!(as_list(&env, joined).unwrap().len(), 4);
+		assert_eq!(__av_add(&mut env, SYMBOL_EMPTY_ARR, SYMBOL_EMPTY_ARR), SYMBOL_EMPTY_ARR);
+
+		let text_copy = env.init_value(Atom::StringValue("row".to_string()));
+		let same = __av_list(&mut env, vec![f64::to_bits(1.0), f64::to_bits(2.0), text_copy]);
+		assert!(values_equal(&env, list, same));
+		assert!(!values_equal(&env, list, joined));
+		assert_eq!(crate::format::repr_value(&env, list), "[1, 2, row]");
+	}
+
+	#[test]
+	fn test_list_literal() {
+		let mut env = Environment::new(APP_SYMBOL_START);
+		let (open, close, comma) = (SYMBOL_OPEN_SQBR.symbol, SYMBOL_CLOSE_SQBR.symbol, SYMBOL_COMMA.symbol);
+		let one = f64::to_bits(1.0);
+		let two = f64::to_bits(2.0);
+
+		// [1, [2], []] followed by another token
+		let tokens = vec![open, one, comma, open, two, close, comma, open, close, close, SYMBOL_PLUS.symbol];
+		let (list, used) = __av_list_literal(&mut env, &tokens).unwrap();
+		assert_eq!(used, 10);
+		assert_eq!(crate::format::repr_value(&env, list), "[1, [2], []]");
+
+		assert_eq!(__av_list_literal(&mut env, &[open, one, comma]), Err(PARSE_ERR_UNMATCHED_PARENS));
+		assert_eq!(__av_list_literal(&mut env, &[open, one, comma, close]), Err(PARSE_ERR_UNEXPECTED_TOKEN));
+		assert_eq!(__av_list_literal(&mut env, &[open, one, two, close]), Err(PARSE_ERR_UNEXPECTED_TOKEN));
+	}
+
+	#[test]
+	fn test_list_iteration() {
+		use crate::functions::{__av_map, __av_filter};
+		let mut env = Environment::new(APP_SYMBOL_START);
+		let list = __av_list(&mut env, vec![f64::to_bits(-1.0), f64::to_bits(0.0), f64::to_bits(2.0)]);
+
+		let absolute = __av_map(&mut env, list, AV_FN_ABS.symbol);
+		assert_eq!(crate::format::repr_value(&env, absolute), "[1, 0, 2]");
+		let nonzero = __av_filter(&mut env, list, AV_FN_ABS.symbol);
+		assert_eq!(crate::format::repr_value(&env, nonzero), "[-1, 2]");
+
+		assert_eq!(__av_map(&mut env, list, AV_FN_MIN.symbol), RUNTIME_ERR_FN_ARITY);
+		assert_eq!(__av_map(&mut env, list, f64::to_bits(1.0)), RUNTIME_ERR_FN_EXPECTED);
+		assert_eq!(__av_map(&mut env, f64::to_bits(1.0), AV_FN_ABS.symbol), RUNTIME_ERR_EXPECTED_LIST);
+		let text = env.init_value(Atom::StringValue("x".to_string()));
+		let mixed = __av_list(&mut env, vec![f64::to_bits(1.0), text]);
+		assert_eq!(__av_map(&mut env, mixed, AV_FN_ABS.symbol), RUNTIME_ERR_EXPECTED_NUM);
+	}
+
+	#[test]
+	fn test_mod_pow_neg() {
+		let mut env = Environment::new(APP_SYMBOL_START);
+		let num = |n: f64| n.to_bits();
+		assert_eq!(__av_mod(&mut env, num(7.0), num(3.0)), num(1.0));
+		assert_eq!(__av_mod(&mut env, num(-7.0), num(3.0)), num(2.0));
+		assert_eq!(__av_mod(&mut env, num(7.0), num(-3.0)), num(-2.0));
+		assert_eq!(__av_mod(&mut env, num(7.0), num(0.0)), RUNTIME_ERR_DIV_Z);
+		assert_eq!(__av_pow(&mut env, num(2.0), num(10.0)), num(1024.0));
+		assert_eq!(__av_pow(&mut env, num(0.0), num(-1.0)), RUNTIME_ERR_DIV_Z);
+		assert_eq!(__av_pow(&mut env, num(-8.0), num(0.5)), RUNTIME_ERR_TYPE_NAN);
+		assert_eq!(__av_neg(&mut env, num(3.0)), num(-3.0));
+		assert_eq!(__av_neg(&mut env, SYMBOL_TRUE.symbol), RUNTIME_ERR_EXPECTED_NUM);
+
+		// Decimals on either side, like the other arithmetic operators
+		let two = env.init_value(Atom::DecimalValue(Decimal::parse("2").unwrap()));
+		let three = env.init_value(Atom::DecimalValue(Decimal::parse("3").unwrap()));
+		let eight = Atom::DecimalValue(Decimal::parse("8").unwrap());
+		let result = __av_pow(&mut env, two, num(3.0));
+		assert_eq!(resolve_atom!(env, result), eight);
+		let result = __av_pow(&mut env, num(2.0), three);
+		assert_eq!(resolve_atom!(env, result), eight);
+		let result = __av_pow(&mut env, two, three);
+		assert_eq!(resolve_atom!(env, result), eight);
+		let half = env.init_value(Atom::DecimalValue(Decimal::parse("0.5").unwrap()));
+		assert_eq!(__av_pow(&mut env, num(4.0), half), num(2.0));
+		assert_eq!(__av_pow(&mut env, SYMBOL_TRUE.symbol, three), RUNTIME_ERR_EXPECTED_NUM);
+	}
+
+	#[test]
+	fn test_text_ordering() {
+		let mut env = Environment::new(APP_SYMBOL_START);
+		let apple = env.init_value(Atom::StringValue("apple".to_string()));
+		let banana = env.init_value(Atom::StringValue("banana".to_string()));
+		let upper = env.init_value(Atom::StringValue("Banana".to_string()));
+		assert_eq!(__av_lt(&mut env, apple, banana), SYMBOL_TRUE.symbol);
+		assert_eq!(__av_gte(&mut env, banana, banana), SYMBOL_TRUE.symbol);
+		// Uppercase letters have smaller scalar values
+		assert_eq!(__av_lt(&mut env, upper, apple), SYMBOL_TRUE.symbol);
+		assert_eq!(__av_lt(&mut env, apple, f64::to_bits(1.0)), RUNTIME_ERR_EXPECTED_NUM);
+
+		assert_eq!(compare_text("straße", "STRASSE", true), Ordering::Equal);
+		assert_eq!(compare_text("b", "B", false), Ordering::Greater);
+		assert_eq!(compare_text("é", "z", false), Ordering::Greater);
+		assert_eq!(total_order(&env, upper, banana, true), Ordering::Equal);
+	}
+
+	#[test]
+	fn test_total_order() {
+		let mut env = Environment::new(APP_SYMBOL_START);
+		let text = env.init_value(Atom::StringValue("text".to_string()));
+		let one = f64::to_bits(1.0);
+		let list_a = __av_list(&mut env, vec![one, text]);
+		let list_b = __av_list(&mut env, vec![one, text, one]);
+		let mut values = vec![
+			RUNTIME_ERR_DIV_Z, SYMBOL_NONE.symbol, list_b, SYMBOL_TRUE.symbol, f64::NAN.to_bits(),
+			text, list_a, SYMBOL_FALSE.symbol, f64::to_bits(-2.0), one
+		];
+		values.sort_by(|a, b| total_order(&env, *a, *b, false));
+		assert_eq!(values, vec![
+			f64::to_bits(-2.0), one, f64::NAN.to_bits(), text, SYMBOL_FALSE.symbol,
+			SYMBOL_TRUE.symbol, list_a, list_b, SYMBOL_NONE.symbol, RUNTIME_ERR_DIV_Z
+		]);
+
+		// NaN sorts after decimals too, so the order stays transitive
+		let nan = f64::NAN.to_bits();
+		let small = env.init_value(Atom::DecimalValue(Decimal::parse("0.5").unwrap()));
+		let large = env.init_value(Atom::DecimalValue(Decimal::parse("5.5").unwrap()));
+		assert_eq!(total_order(&env, nan, small, false), Ordering::Greater);
+		assert_eq!(total_order(&env, large, nan, false), Ordering::Less);
+		let mut numbers = vec![nan, large, f64::to_bits(2.0), small];
+		numbers.sort_by(|a, b| total_order(&env, *a, *b, false));
+		assert_eq!(numbers, vec![small, f64::to_bits(2.0), large, nan]);
+
+		// Cells holding numbers sort by their values, not their pointers
+		let cells: Vec<u64> = [30.0, -1.5, f64::NAN, 2.0].iter()
+			.map(|num| env.init_value(Atom::NumericValue(*num)))
+			.collect();
+		let mut sorted = vec![cells[0], cells[1], cells[2], cells[3], f64::to_bits(10.0), small];
+		sorted.sort_by(|a, b| total_order(&env, *a, *b, false));
+		assert_eq!(sorted, vec![cells[1], small, cells[3], f64::to_bits(10.0), cells[0], cells[2]]);
+		let list = __av_list(&mut env, cells.clone());
+		let result = crate::functions::__av_sort(&mut env, list);
+		assert_eq!(as_list(&env, result), Some(vec![cells[1], cells[3], cells[0], cells[2]]));
+		let compared = crate::functions::__av_compare(&mut env, cells[0], cells[3]);
+		assert_eq!(compared, f64::to_bits(1.0));
+	}
+
+	#[test]
+	fn test_short_circuit() {
+		let mut env = Environment::new(APP_SYMBOL_START);
+		let zero = f64::to_bits(0.0);
+		let mut evaluated = false;
+		let mut divide = |env: &mut Environment| {
+			evaluated = true;
+			__av_div(env, f64::to_bits(10.0), zero)
+		};
+		assert_eq!(apply_lazy(&mut env, &SYMBOL_AND, SYMBOL_FALSE.symbol, &mut divide), SYMBOL_FALSE.symbol);
+		assert_eq!(apply_lazy(&mut env, &SYMBOL_OR, SYMBOL_TRUE.symbol, &mut divide), SYMBOL_TRUE.symbol);
+		assert_eq!(evaluated, false);
+
+		assert_eq!(apply_lazy(&mut env, &SYMBOL_AND, SYMBOL_TRUE.symbol, &mut |env| __av_div(env, f64::to_bits(10.0), zero)), RUNTIME_ERR_DIV_Z);
+		assert_eq!(apply_lazy(&mut env, &SYMBOL_PLUS, f64::to_bits(1.0), &mut |_| f64::to_bits(2.0)), f64::to_bits(3.0));
+
+		// Errors propagate instead of becoming False
+		assert_eq!(__av_and(&mut env, RUNTIME_ERR_DIV_Z, SYMBOL_TRUE.symbol), RUNTIME_ERR_DIV_Z);
+		assert_eq!(__av_or(&mut env, RUNTIME_ERR_DIV_Z, SYMBOL_TRUE.symbol), RUNTIME_ERR_DIV_Z);
+		assert_eq!(__av_or(&mut env, SYMBOL_FALSE.symbol, RUNTIME_ERR_DIV_Z), RUNTIME_ERR_DIV_Z);
+		assert_eq!(__av_and(&mut env, f64::to_bits(2.0), SYMBOL_TRUE.symbol), SYMBOL_TRUE.symbol);
+	}
+
+	#[test]
+	fn test_error_propagation() {
+		let mut env = Environment::new(APP_SYMBOL_START);
+		let one = f64::to_bits(1.0);
+		let text = env.init_value(Atom::StringValue("text".to_string()));
+		let upstream = with_error_origin(RUNTIME_ERR_DIV_Z, APP_SYMBOL_START | VALUE_T_PTR_OBJ);
+		assert_eq!(__av_add(&mut env, RUNTIME_ERR_CIRCULAR_DEP, one), RUNTIME_ERR_CIRCULAR_DEP);
+		assert_eq!(__av_add(&mut env, text, upstream), upstream);
+		assert_eq!(__av_mul(&mut env, one, upstream), upstream);
+		assert_eq!(__av_lt(&mut env, upstream, text), upstream);
+		assert_eq!(__av_neg(&mut env, upstream), upstream);
+		assert_eq!(__av_not(&mut env, upstream), upstream);
+		assert_eq!(__av_eq(&mut env, upstream, upstream), upstream);
+		assert_eq!(__av_dot(&mut env, upstream, AV_PROP_LENGTH.symbol), upstream);
+		// Still reported for values that aren't errors
+		assert_eq!(__av_sub(&mut env, text, one), RUNTIME_ERR_EXPECTED_NUM);
+	}
+
+	#[test]
+	fn test_truthiness() {
+		let mut env = Environment::new(APP_SYMBOL_START);
+		let empty_text = env.init_value(Atom::StringValue("".to_string()));
+		let text = env.init_value(Atom::StringValue("hello world".to_string()));
+		let list = __av_list(&mut env, vec![SYMBOL_FALSE.symbol]);
+		let map = env.init_value(Atom::HashMapValue(AvObject::new_table(0)));
+		let empty_obj = env.init_value(Atom::ObjectValue(AvObject::new_table(0)));
+		let mut flagged = AvObject::new_table(1);
+		flagged.table_insert(AV_PROP_BOOL.symbol, SYMBOL_FALSE.symbol).unwrap();
+		let flagged = env.init_value(Atom::ObjectValue(flagged));
+		// Flags stored as pointers are resolved like any other value
+		let true_cell = env.init_value(Atom::SymbolValue(SYMBOL_TRUE.symbol));
+		let mut pointer_flag = AvObject::new_table(1);
+		pointer_flag.table_insert(AV_PROP_BOOL.symbol, true_cell).unwrap();
+		let pointer_flag = env.init_value(Atom::ObjectValue(pointer_flag));
+		let mut text_flag = AvObject::new_table(1);
+		text_flag.table_insert(AV_PROP_BOOL.symbol, empty_text).unwrap();
+		let text_flag = env.init_value(Atom::ObjectValue(text_flag));
+		let mut entry = AvObject::new_table(1);
+		entry.table_insert(AV_PROP_LENGTH.symbol, f64::to_bits(0.0)).unwrap();
+		let entry = env.init_value(Atom::ObjectValue(entry));
+
+		for falsy in [empty_text, SYMBOL_EMPTY_STR, SYMBOL_EMPTY_ARR, map, empty_obj, flagged, text_flag,
+				SYMBOL_NONE.symbol, SYMBOL_FALSE.symbol, f64::to_bits(0.0)].iter() {
+			assert_eq!(__av_not(&mut env, *falsy), SYMBOL_TRUE.symbol, "{:X}", falsy);
+		}
+		for truthy in [text, list, entry, pointer_flag, SYMBOL_TRUE.symbol, f64::to_bits(-1.0)].iter() {
+			assert_eq!(__av_not(&mut env, *truthy), SYMBOL_FALSE.symbol, "{:X}", truthy);
+		}
+		assert_eq!(__av_or(&mut env, empty_text, list), SYMBOL_TRUE.symbol);
+		assert_eq!(__av_and(&mut env, text, map), SYMBOL_FALSE.symbol);
+	}
+
+	#[test]
+	fn test_coalesce() {
+		let mut env = Environment::new(APP_SYMBOL_START);
+		let one = f64::to_bits(1.0);
+		assert_eq!(__av_coalesce(&mut env, SYMBOL_NONE.symbol, one), one);
+		assert_eq!(__av_coalesce(&mut env, SYMBOL_FALSE.symbol, one), SYMBOL_FALSE.symbol);
+		assert_eq!(__av_coalesce(&mut env, RUNTIME_ERR_DIV_Z, one), RUNTIME_ERR_DIV_Z);
+		let mut evaluated = false;
+		assert_eq!(apply_lazy(&mut env, &SYMBOL_COALESCE, one, &mut |_| { evaluated = true; SYMBOL_NONE.symbol }), one);
+		assert_eq!(evaluated, false);
+
+		use crate::functions::{__av_iferror, __av_catch};
+		let tagged = with_error_origin(RUNTIME_ERR_DIV_Z, APP_SYMBOL_START | VALUE_T_PTR_OBJ);
+		assert_eq!(__av_iferror(&mut env, tagged, one), one);
+		assert_eq!(__av_iferror(&mut env, SYMBOL_NONE.symbol, one), SYMBOL_NONE.symbol);
+		assert_eq!(__av_catch(&mut env, tagged, RUNTIME_ERR_DIV_Z, one), one);
+		assert_eq!(__av_catch(&mut env, RUNTIME_ERR_EXPECTED_NUM, RUNTIME_ERR_DIV_Z, one), RUNTIME_ERR_EXPECTED_NUM);
+		assert_eq!(__av_catch(&mut env, tagged, SYMBOL_NONE.symbol, one), tagged);
+		// Callable from expressions as catch(value, error, fallback)
+		if let Atom::FunctionValue(catch) = &AV_FN_CATCH.value {
+			assert_eq!(catch.call(&mut env, vec![tagged, RUNTIME_ERR_DIV_Z, one]), one);
+		} else {
+			panic!("catch should be a function");
+		}
+
+		// Names bound to None are None
+		let none_cell = env.init_value(Atom::SymbolValue(SYMBOL_NONE.symbol));
+		let alias = env.init_value(Atom::SymbolValue(none_cell));
+		let text = env.init_value(Atom::StringValue("text".to_string()));
+		assert_eq!(__av_coalesce(&mut env, none_cell, one), one);
+		assert_eq!(__av_coalesce(&mut env, alias, one), one);
+		assert_eq!(__av_coalesce(&mut env, text, one), text);
+	}
+
+	#[test]
+	fn test_equality() {
+		let mut env = Environment::new(APP_SYMBOL_START);
+		let nan = f64::NAN.to_bits();
+		assert_eq!(__av_eq(&mut env, f64::to_bits(0.0), f64::to_bits(-0.0)), SYMBOL_TRUE.symbol);
+		assert_eq!(__av_eq(&mut env, nan, nan), SYMBOL_FALSE.symbol);
+		assert_eq!(__av_ne(&mut env, nan, nan), SYMBOL_TRUE.symbol);
+		assert_eq!(__av_eq(&mut env, SYMBOL_TRUE.symbol, SYMBOL_TRUE.symbol), SYMBOL_TRUE.symbol);
+		assert_eq!(__av_eq(&mut env, SYMBOL_NONE.symbol, SYMBOL_FALSE.symbol), SYMBOL_FALSE.symbol);
+
+		// Heap vs inline strings
+		let heap_str = env.init_value(Atom::StringValue("abc".to_string()));
+		let inline_str = crate::utils::create_small_string("abc").unwrap();
+		let empty_str = env.init_value(Atom::StringValue(String::new()));
+		assert_eq!(__av_eq(&mut env, heap_str, inline_str), SYMBOL_TRUE.symbol);
+		assert_eq!(__av_eq(&mut env, empty_str, SYMBOL_EMPTY_STR), SYMBOL_TRUE.symbol);
+
+		// Across types is false rather than an error
+		assert_eq!(__av_eq(&mut env, heap_str, f64::to_bits(1.0)), SYMBOL_FALSE.symbol);
+		assert_eq!(__av_eq(&mut env, SYMBOL_TRUE.symbol, f64::to_bits(1.0)), SYMBOL_FALSE.symbol);
+
+		let dec = env.init_value(Atom::DecimalValue(Decimal::parse("0.10").unwrap()));
+		assert_eq!(__av_eq(&mut env, dec, f64::to_bits(0.1)), SYMBOL_TRUE.symbol);
+
+		let text_a = env.init_value(Atom::StringValue("x".to_string()));
+		let text_b = env.init_value(Atom::StringValue("x".to_string()));
+		let mut map_a = AvObject::new_table(1);
+		map_a.table_insert(1, text_a).unwrap();
+		let mut map_b = AvObject::new_table(1);
+		map_b.table_insert(1, text_b).unwrap();
+		let map_a = env.init_value(Atom::HashMapValue(map_a));
+		let map_b = env.init_value(Atom::HashMapValue(map_b));
+		assert_eq!(__av_eq(&mut env, map_a, map_b), SYMBOL_TRUE.symbol);
+
+		let fn_a = env.init_value(AV_FN_ABS.value.clone());
+		let fn_b = env.init_value(AV_FN_ABS.value.clone());
+		let fn_c = env.init_value(AV_FN_CEIL.value.clone());
+		assert_eq!(__av_eq(&mut env, fn_a, fn_b), SYMBOL_TRUE.symbol);
+		assert_eq!(__av_eq(&mut env, fn_a, fn_c), SYMBOL_FALSE.symbol);
+
+		// Unbound and self-referential names are only equal to themselves
+		let unbound_a = env.define_identifier().unwrap();
+		let unbound_b = env.define_identifier().unwrap();
+		assert_eq!(__av_eq(&mut env, unbound_a, unbound_a), SYMBOL_TRUE.symbol);
+		assert_eq!(__av_eq(&mut env, unbound_a, unbound_b), SYMBOL_FALSE.symbol);
+		let cycle_a = env.define_identifier().unwrap();
+		let cycle_b = env.define_identifier().unwrap();
+		env.bind_value(cycle_a, Atom::SymbolValue(cycle_a));
+		env.bind_value(cycle_b, Atom::SymbolValue(cycle_b));
+		assert_eq!(__av_eq(&mut env, cycle_a, cycle_b), SYMBOL_FALSE.symbol);
+		assert_eq!(__av_eq(&mut env, cycle_a, unbound_a), SYMBOL_FALSE.symbol);
+	}
+
+	#[test]
+	fn test_dot_access() {
+		let mut env = Environment::new(APP_SYMBOL_START);
+		let field = env.define_identifier().unwrap();
+		let missing = env.define_identifier().unwrap();
+
+		let mut obj = AvObject::new_table(1);
+		obj.table_insert(field, f64::to_bits(42.0)).unwrap();
+		let obj_symbol = env.init_value(Atom::ObjectValue(obj));
+		assert_eq!(__av_dot(&mut env, obj_symbol, field), f64::to_bits(42.0));
+		assert_eq!(__av_dot(&mut env, obj_symbol, missing), RUNTIME_ERR_UNK_FIELD);
+
+		let value = env.init_value(Atom::StringValue("value".to_string()));
+		let mut map = AvObject::new_table(1);
+		map.table_insert(field, value).unwrap();
+		let map_symbol = env.init_value(Atom::HashMapValue(map));
+		let result = __av_dot(&mut env, map_symbol, field);
+		assert_eq!(resolve_atom!(env, result), Atom::StringValue("value".to_string()));
+
+		let text = env.init_value(Atom::StringValue("hello".to_string()));
+		assert_eq!(__av_dot(&mut env, text, AV_PROP_LENGTH.symbol), f64::to_bits(5.0));
+		assert_eq!(__av_dot(&mut env, SYMBOL_EMPTY_STR, AV_PROP_LENGTH.symbol), f64::to_bits(0.0));
+		let short = crate::utils::create_small_string("abc").unwrap();
+		assert_eq!(__av_dot(&mut env, short, AV_PROP_LENGTH.symbol), f64::to_bits(3.0));
+		assert_eq!(__av_dot(&mut env, short, field), RUNTIME_ERR_UNK_FIELD);
+		assert_eq!(__av_dot(&mut env, f64::to_bits(1.0), field), RUNTIME_ERR_INVALID_TYPE);
+	}
+}
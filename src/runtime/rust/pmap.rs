@@ -0,0 +1,326 @@
+// Persistent hash array mapped trie over u64 keys. Like the hamt package on the JS side.
+// Clones are O(1) and share structure. Updates copy only the path to the changed entry,
+// so many versions of a large map can be kept cheaply. Used for Environment snapshots.
+//
+// Keys are symbols, which are mostly sequential, so their low bits are used directly as the hash.
+
+use alloc::vec::Vec;
+use std::rc::Rc;
+
+
+const BITS_PER_LEVEL: u32 = 5;
+const LEVEL_MASK: u64 = (1 << BITS_PER_LEVEL) - 1;
+
+#[derive(Clone)]
+enum Node<V: Clone> {
+    // bitmap marks which of the 32 slots are present. children are stored densely.
+    Branch { bitmap: u32, children: Vec<Rc<Node<V>>> },
+    Leaf { key: u64, value: V }
+}
+
+#[derive(Clone)]
+pub struct PersistentMap<V: Clone> {
+    root: Option<Rc<Node<V>>>,
+    len: usize
+}
+
+#[inline(always)]
+fn slot(key: u64, shift: u32) -> u32 {
+    return ((key >> shift) & LEVEL_MASK) as u32;
+}
+
+#[inline(always)]
+fn dense_index(bitmap: u32, bit: u32) -> usize {
+    return (bitmap & ((1 << bit) - 1)).count_ones() as usize;
+}
+
+// Branch holding two leaves whose keys are equal up to this level
+fn split<V: Clone>(existing: Rc<Node<V>>, existing_key: u64, key: u64, value: V, shift: u32) -> Node<V> {
+    let slot_existing = slot(existing_key, shift);
+    let slot_new = slot(key, shift);
+    if slot_existing == slot_new {
+        let child = split(existing, existing_key, key, value, shift + BITS_PER_LEVEL);
+        return Node::Branch { bitmap: 1 << slot_new, children: vec![Rc::new(child)] };
+    }
+    let leaf = Rc::new(Node::Leaf { key: key, value: value });
+    let children = if slot_existing < slot_new { vec![existing, leaf] } else { vec![leaf, existing] };
+    return Node::Branch { bitmap: (1 << slot_existing) | (1 << slot_new), children: children };
+}
+
+fn insert_node<V: Clone>(node: &mut Rc<Node<V>>, key: u64, value: V, shift: u32) -> Option<V> {
+    let leaf_key = match node.as_ref() {
+        Node::Leaf { key: leaf_key, .. } => Some(*leaf_key),
+        Node::Branch { .. } => None
+    };
+    if let Some(leaf_key) = leaf_key {
+        if leaf_key == key {
+            if let Node::Leaf { value: existing, .. } = Rc::make_mut(node) {
+                return Some(core::mem::replace(existing, value));
+            }
+            return None;
+        }
+        let split_node = split(Rc::clone(node), leaf_key, key, value, shift);
+        *node = Rc::new(split_node);
+        return None;
+    }
+
+    if let Node::Branch { bitmap, children } = Rc::make_mut(node) {
+        let bit = slot(key, shift);
+        let index = dense_index(*bitmap, bit);
+        if *bitmap & (1 << bit) == 0 {
+            *bitmap |= 1 << bit;
+            children.insert(index, Rc::new(Node::Leaf { key: key, value: value }));
+            return None;
+        }
+        return insert_node(&mut children[index], key, value, shift + BITS_PER_LEVEL);
+    }
+    return None;
+}
+
+// Returns the removed value. The node is set to None when it becomes empty.
+fn remove_node<V: Clone>(node: &mut Option<Rc<Node<V>>>, key: u64, shift: u32) -> Option<V> {
+    let is_leaf = match node.as_deref() {
+        None => return None,
+        Some(Node::Leaf { key: leaf_key, .. }) => {
+            if *leaf_key != key {
+                return None;
+            }
+            true
+        },
+        Some(Node::Branch { bitmap, .. }) => {
+            if *bitmap & (1 << slot(key, shift)) == 0 {
+                return None;
+            }
+            false
+        }
+    };
+    if is_leaf {
+        let leaf = node.take()?;
+        return match Rc::try_unwrap(leaf).unwrap_or_else(|shared| (*shared).clone()) {
+            Node::Leaf { value, .. } => Some(value),
+            Node::Branch { .. } => None
+        };
+    }
+
+    // Some(None) when the branch is now empty. Some(leaf) when it only holds one leaf.
+    let mut replacement: Option<Option<Rc<Node<V>>>> = None;
+    let mut removed = None;
+    if let Some(current) = node.as_mut() {
+        if let Node::Branch { bitmap, children } = Rc::make_mut(current) {
+            let bit = slot(key, shift);
+            let index = dense_index(*bitmap, bit);
+            let mut child = Some(Rc::clone(&children[index]));
+            removed = remove_node(&mut child, key, shift + BITS_PER_LEVEL);
+            match child {
+                Some(child) => children[index] = child,
+                None => {
+                    children.remove(index);
+                    *bitmap &= !(1 << bit);
+                }
+            }
+            if children.is_empty() {
+                replacement = Some(None);
+            } else if children.len() == 1 {
+                if let Node::Leaf { .. } = children[0].as_ref() {
+                    replacement = Some(Some(Rc::clone(&children[0])));
+                }
+            }
+        }
+    }
+    if let Some(new_node) = replacement {
+        *node = new_node;
+    }
+    return removed;
+}
+
+impl<V: Clone> PersistentMap<V> {
+    pub fn new() -> PersistentMap<V> {
+        return PersistentMap { root: None, len: 0 };
+    }
+
+    pub fn len(&self) -> usize {
+        return self.len;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len == 0;
+    }
+
+    pub fn get(&self, key: &u64) -> Option<&V> {
+        let mut node = self.root.as_ref()?;
+        let mut shift = 0;
+        loop {
+            match node.as_ref() {
+                Node::Leaf { key: leaf_key, value } => {
+                    return if leaf_key == key { Some(value) } else { None };
+                },
+                Node::Branch { bitmap, children } => {
+                    let bit = slot(*key, shift);
+                    if bitmap & (1 << bit) == 0 {
+                        return None;
+                    }
+                    node = &children[dense_index(*bitmap, bit)];
+                    shift += BITS_PER_LEVEL;
+                }
+            }
+        }
+    }
+
+    pub fn contains_key(&self, key: &u64) -> bool {
+        return self.get(key).is_some();
+    }
+
+    // Copies the nodes along the path if they're shared with another version.
+    pub fn get_mut(&mut self, key: &u64) -> Option<&mut V> {
+        if !self.contains_key(key) {
+            return None;
+        }
+        let mut node = self.root.as_mut()?;
+        let mut shift = 0;
+        loop {
+            match Rc::make_mut(node) {
+                Node::Leaf { value, .. } => return Some(value),
+                Node::Branch { bitmap, children } => {
+                    let bit = slot(*key, shift);
+                    node = &mut children[dense_index(*bitmap, bit)];
+                    shift += BITS_PER_LEVEL;
+                }
+            }
+        }
+    }
+
+    pub fn insert(&mut self, key: u64, value: V) -> Option<V> {
+        let previous = match self.root.as_mut() {
+            Some(root) => insert_node(root, key, value, 0),
+            None => {
+                self.root = Some(Rc::new(Node::Leaf { key: key, value: value }));
+                None
+            }
+        };
+        if previous.is_none() {
+            self.len += 1;
+        }
+        return previous;
+    }
+
+    pub fn remove(&mut self, key: &u64) -> Option<V> {
+        let removed = remove_node(&mut self.root, *key, 0);
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        return removed;
+    }
+
+    pub fn iter(&self) -> PersistentMapIter<'_, V> {
+        let mut stack = Vec::new();
+        if let Some(root) = &self.root {
+            stack.push(root.as_ref());
+        }
+        return PersistentMapIter { stack: stack };
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &u64> {
+        return self.iter().map(|(key, _)| key);
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        return self.iter().map(|(_, value)| value);
+    }
+
+    // Whether both versions are the same, without comparing entries
+    pub fn ptr_eq(&self, other: &PersistentMap<V>) -> bool {
+        match (&self.root, &other.root) {
+            (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false
+        }
+    }
+}
+
+impl<V: Clone> Default for PersistentMap<V> {
+    fn default() -> Self {
+        return PersistentMap::new();
+    }
+}
+
+impl<V: Clone + core::fmt::Debug> core::fmt::Debug for PersistentMap<V> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// Depth-first. Order follows the key bits, not insertion order.
+pub struct PersistentMapIter<'a, V: Clone> {
+    stack: Vec<&'a Node<V>>
+}
+
+impl<'a, V: Clone> Iterator for PersistentMapIter<'a, V> {
+    type Item = (&'a u64, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            match node {
+                Node::Leaf { key, value } => return Some((key, value)),
+                Node::Branch { children, .. } => {
+                    for child in children.iter().rev() {
+                        self.stack.push(child.as_ref());
+                    }
+                }
+            }
+        }
+        return None;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_get_remove() {
+        let mut map: PersistentMap<u64> = PersistentMap::new();
+        for key in 0..2000 {
+            assert_eq!(map.insert(key * 7, key), None);
+        }
+        assert_eq!(map.insert(14, 99), Some(2));
+        assert_eq!(map.len(), 2000);
+        assert_eq!(map.get(&14), Some(&99));
+        assert_eq!(map.get(&15), None);
+        // Keys which only differ in the high bits
+        map.insert(1 << 62, 1);
+        map.insert((1 << 62) | (1 << 61), 2);
+        assert_eq!(map.get(&(1 << 62)), Some(&1));
+
+        for key in 0..2000 {
+            assert!(map.remove(&(key * 7)).is_some());
+        }
+        assert_eq!(map.remove(&7), None);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.iter().count(), 2);
+    }
+
+    #[test]
+    fn test_structural_sharing() {
+        let mut map: PersistentMap<u64> = PersistentMap::new();
+        for key in 0..100 {
+            map.insert(key, key);
+        }
+        let snapshot = map.clone();
+        assert!(snapshot.ptr_eq(&map));
+
+        *map.get_mut(&5).unwrap() = 500;
+        map.remove(&6);
+        map.insert(1000, 1);
+        assert_eq!(map.get(&5), Some(&500));
+        assert_eq!(snapshot.get(&5), Some(&5));
+        assert_eq!(snapshot.get(&6), Some(&6));
+        assert_eq!(snapshot.get(&1000), None);
+        assert_eq!(snapshot.len(), 100);
+        assert_eq!(map.len(), 100);
+
+        let mut keys: Vec<u64> = snapshot.keys().cloned().collect();
+        keys.sort();
+        assert_eq!(keys, (0..100).collect::<Vec<u64>>());
+    }
+}
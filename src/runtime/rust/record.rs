@@ -0,0 +1,191 @@
+// User-defined record types. i.e. Customer { name, email, plan: "free" }
+//
+// A class is an AvObject with av_class = AV_CLASS_CLASS. Its values are a hash table
+// of field symbol -> default value. Instances are AvObjects whose av_class is the
+// symbol of their class, with a value for every declared field.
+
+use crate::environment::{Environment, validate_name};
+use crate::structs::{Atom, AvObject};
+use crate::constants::*;
+use crate::types::*;
+use crate::hashtable::{table_new, table_get, table_insert};
+use crate::operators::resolve_ref;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+
+// A class field declaration. Fields without a default must be given on construction.
+pub struct FieldDef {
+    pub name: String,
+    pub default: Option<u64>
+}
+
+impl FieldDef {
+    pub fn new(name: &str, default: Option<u64>) -> FieldDef {
+        return FieldDef {
+            name: name.to_string(),
+            default: default
+        }
+    }
+}
+
+
+// Declare a class and bind it to the given name. Returns the class symbol.
+pub fn define_class(env: &mut Environment, name: &str, fields: Vec<FieldDef>) -> Result<u64, u64> {
+    env.check_name(name).map_err(|err| err.error_code())?;
+    // Checked before any field symbols are allocated
+    for field in fields.iter() {
+        validate_name(&field.name).map_err(|err| err.error_code())?;
+    }
+
+    let mut field_table = table_new(fields.len());
+    for field in fields.iter() {
+        let field_symbol = env.field_symbol(&field.name)?;
+        let default = field.default.unwrap_or(SYMBOL_REQUIRED_FIELD);
+        if table_insert(&mut field_table, field_symbol, default)?.is_some() {
+            // Same field declared twice
+            return Err(PARSE_ERR_USED_NAME);
+        }
+    }
+
+    // Allocated once the fields are valid, so failed declarations don't use up symbols
+    let class_symbol = env.define_identifier()?;
+    let mut class_obj = AvObject::new_instance(class_symbol, AV_CLASS_CLASS);
+    class_obj.av_values = Some(field_table);
+
+    env.bind_value(class_symbol, Atom::ObjectValue(class_obj));
+    env.bind_name(class_symbol, name.to_string())?;
+    return Ok(class_symbol);
+}
+
+// Resolve a value to a class object
+pub fn resolve_class(env: &Environment, class: u64) -> Option<&AvObject> {
+    match resolve_ref(env, class) {
+        Some(Atom::ObjectValue(obj)) if obj.av_class == AV_CLASS_CLASS => Some(obj),
+        _ => None
+    }
+}
+
+// Field table for a new instance, with defaults filled in
+fn instance_values(class_obj: &AvObject, fields: Vec<(u64, u64)>) -> Result<Vec<u64>, u64> {
+    let mut values = table_new(class_obj.table_len());
+    for (field, value) in fields {
+        if class_obj.table_get(field).is_none() {
+            return Err(RUNTIME_ERR_UNK_FIELD);
+        }
+        table_insert(&mut values, field, value)?;
+    }
+
+    for (field, default) in class_obj.table_iter() {
+        if table_get(&values, field).is_none() {
+            if default == SYMBOL_REQUIRED_FIELD {
+                return Err(RUNTIME_ERR_MISSING_FIELD);
+            }
+            table_insert(&mut values, field, default)?;
+        }
+    }
+    return Ok(values);
+}
+
+// Construct an instance of a class. Target of construction syntax. Customer(name: "Ada")
+// Every given field must be declared, and every field without a default must be given.
+pub fn __av_new(env: &mut Environment, class: u64, fields: Vec<(u64, u64)>) -> u64 {
+    propagate_errors!(class);
+    for (_, value) in fields.iter() {
+        propagate_errors!(*value);
+    }
+    // The class is read in place rather than copied
+    let (class_id, values) = match resolve_class(env, class) {
+        Some(class_obj) => match instance_values(class_obj, fields) {
+            Ok(values) => (class_obj.id, values),
+            Err(err) => return err
+        },
+        None => return RUNTIME_ERR_EXPECTED_CLASS
+    };
+
+    // Only allocated for valid instances. Unbound symbols can't be reclaimed by the GC.
+    let instance_symbol = match env.define_identifier() {
+        Ok(symbol) => symbol,
+        Err(err) => return err
+    };
+    let mut instance = AvObject::new_instance(instance_symbol, class_id);
+    instance.av_values = Some(values);
+    env.bind_value(instance_symbol, Atom::ObjectValue(instance));
+    return instance_symbol;
+}
+
+// is(obj, Class). Whether the value is an instance of the class.
+pub fn __av_is(env: &mut Environment, obj: u64, class: u64) -> u64 {
+    propagate_errors!(obj, class);
+    let class_id = match resolve_class(env, class) {
+        Some(class_obj) => class_obj.id,
+        None => return RUNTIME_ERR_EXPECTED_CLASS
+    };
+    if let Some(Atom::ObjectValue(instance)) = resolve_ref(env, obj) {
+        return __repr_bool(instance.av_class == class_id);
+    }
+    return SYMBOL_FALSE.symbol;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operators::__av_dot;
+
+    #[test]
+    fn test_define_and_construct() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let customer = define_class(&mut env, "Customer", vec![
+            FieldDef::new("name", None),
+            FieldDef::new("plan", Some(f64::to_bits(1.0)))
+        ]).unwrap();
+        assert_eq!(env.lookup_by_name("customer".to_string()), Some(&customer));
+        assert_eq!(define_class(&mut env, "CUSTOMER", vec![]), Err(PARSE_ERR_USED_NAME));
+
+        let name = env.field_symbol("name").unwrap();
+        let plan = env.field_symbol("Plan").unwrap();
+        let ada = __av_new(&mut env, customer, vec![(name, f64::to_bits(7.0))]);
+        assert_eq!(__av_dot(&mut env, ada, name), f64::to_bits(7.0));
+        assert_eq!(__av_dot(&mut env, ada, plan), f64::to_bits(1.0));
+        assert_eq!(__av_is(&mut env, ada, customer), SYMBOL_TRUE.symbol);
+        assert_eq!(__av_is(&mut env, f64::to_bits(1.0), customer), SYMBOL_FALSE.symbol);
+
+        if let Atom::ObjectValue(obj) = resolve_atom!(env, ada) {
+            assert_eq!(obj.av_class, customer);
+        } else {
+            panic!("Expected an object");
+        }
+    }
+
+    #[test]
+    fn test_field_validation() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let order = define_class(&mut env, "Order", vec![FieldDef::new("total", None)]).unwrap();
+        let other = env.field_symbol("other").unwrap();
+        assert_eq!(__av_new(&mut env, order, vec![]), RUNTIME_ERR_MISSING_FIELD);
+        assert_eq!(__av_new(&mut env, order, vec![(other, 0)]), RUNTIME_ERR_UNK_FIELD);
+        assert_eq!(__av_new(&mut env, f64::to_bits(1.0), vec![]), RUNTIME_ERR_EXPECTED_CLASS);
+        assert_eq!(define_class(&mut env, "Pair", vec![FieldDef::new("a", None), FieldDef::new("A", None)]), Err(PARSE_ERR_USED_NAME));
+
+        // Field errors are passed through rather than stored
+        let total = env.field_symbol("total").unwrap();
+        assert_eq!(__av_new(&mut env, order, vec![(total, RUNTIME_ERR_DIV_Z)]), RUNTIME_ERR_DIV_Z);
+
+        // Failed constructions don't use up symbols
+        let previous = env.define_identifier().unwrap();
+        assert_eq!(__av_new(&mut env, order, vec![(other, 0)]), RUNTIME_ERR_UNK_FIELD);
+        assert_eq!(__av_new(&mut env, order, vec![]), RUNTIME_ERR_MISSING_FIELD);
+        assert_eq!(__av_new(&mut env, order, vec![(total, 0)]), previous + 1);
+
+        // Field names follow the same rules as other names
+        let next = env.define_identifier().unwrap();
+        assert_eq!(define_class(&mut env, "Blank", vec![FieldDef::new("", None)]), Err(PARSE_ERR_INVALID_NAME));
+        assert_eq!(define_class(&mut env, "Branch", vec![FieldDef::new("ok", None), FieldDef::new("if", None)]), Err(PARSE_ERR_USED_NAME));
+        assert_eq!(env.lookup_by_name("Branch".to_string()), None);
+        assert_eq!(env.define_identifier().unwrap(), next + 1);
+
+        // The class itself doesn't expose its defaults or the required field marker
+        assert_eq!(__av_dot(&mut env, order, total), RUNTIME_ERR_INVALID_TYPE);
+    }
+}
@@ -6,7 +6,7 @@ use fnv::FnvHashMap;
 
 // Ordered by their symbol ID for table lookup
 #[cfg(not(target_os = "unknown"))]
-pub const RESERVED_SYMBOLS: [&'static Keyword; 29] = [ 
+pub const RESERVED_SYMBOLS: [&'static Keyword; 31] = [ 
     &SYMBOL_COMMA, &SYMBOL_EQUALS,
     &SYMBOL_OR, &SYMBOL_AND, &SYMBOL_NOT, 
     &SYMBOL_DBL_EQUALS, &SYMBOL_NOT_EQUALS, 
@@ -18,15 +18,25 @@ pub const RESERVED_SYMBOLS: [&'static Keyword; 29] = [
     &SYMBOL_OPEN_BRACE, &SYMBOL_CLOSE_BRACE, 
     &SYMBOL_COLON, &SYMBOL_SEMI_COLON, 
     &SYMBOL_TRUE, &SYMBOL_FALSE, &SYMBOL_NONE,
-    &SYMBOL_CALL_FN
+    &SYMBOL_CALL_FN, &SYMBOL_POW, &SYMBOL_COALESCE
 ];
 
-pub const BUILTIN_MODULES: [&'static Module; 8] = [
+pub const BUILTIN_MODULES: [&'static Module; 32] = [
     &AV_FN_MIN, &AV_FN_MAX, &AV_FN_ABS, &AV_FN_CEIL, 
-    &AV_FN_FLOOR, &AV_FN_TRUNC, &AV_FN_ROUND, &AV_FN_SQRT
+    &AV_FN_FLOOR, &AV_FN_TRUNC, &AV_FN_ROUND, &AV_FN_SQRT,
+    &AV_FN_DECIMAL, &AV_FN_ROUND_TO, &AV_FN_DIV,
+    &AV_FN_DATE, &AV_FN_TODAY, &AV_FN_WEEKDAY, &AV_FN_ADD_DAYS, &AV_FN_DIFF, &AV_FN_DAYS,
+    &AV_FN_LEN, &AV_FN_SLICE, &AV_FN_MAP, &AV_FN_FILTER, &AV_FN_IS,
+    &AV_FN_TYPE, &AV_FN_IS_NUMBER, &AV_FN_IS_TEXT, &AV_FN_IS_ERROR,
+    &AV_FN_COMPARE, &AV_FN_COMPARE_IGNORE_CASE, &AV_FN_SORT,
+    &AV_FN_IFERROR, &AV_FN_ISERROR, &AV_FN_CATCH
 ];
 // todo: path, query
 
+pub const BUILTIN_PROPERTIES: [&'static Keyword; 5] = [
+    &AV_PROP_LENGTH, &AV_PROP_YEAR, &AV_PROP_MONTH, &AV_PROP_DAY, &AV_PROP_BOOL
+];
+
 // Exclude from WASM code
 #[cfg(not(target_os = "unknown"))]
 lazy_static! {
@@ -43,6 +53,19 @@ lazy_static! {
         // Internal signal. Defined here for recognition by interpreter. 
         // Not defined in SYMBOL_ID_MAP because it's not meant to be used frontend.
         m.insert(SYMBOL_CALL_FN.symbol, &SYMBOL_CALL_FN);
+
+        for property in BUILTIN_PROPERTIES.iter() {
+            m.insert(property.symbol, *property);
+        }
+        m
+    };
+
+    // Used when resolving the name after a dot. Case-insensitive, like other names.
+    pub static ref PROPERTY_ID_MAP: FnvHashMap<String, &'static Keyword> = {
+        let mut m = FnvHashMap::with_capacity_and_hasher(BUILTIN_PROPERTIES.len(), Default::default());
+        for property in BUILTIN_PROPERTIES.iter() {
+            m.insert(property.name.to_string().to_uppercase(), *property);
+        }
         m
     };
 
@@ -77,7 +100,8 @@ lazy_static! {
         m.insert(PARSE_ERR_UNKNOWN_TOKEN, "There's an unknown token in this expression.");
         m.insert(PARSE_ERR_UNEXPECTED_TOKEN, "There's a token in an unexpected location in this expression.");
         m.insert(PARSE_ERR_UNMATCHED_PARENS, "Arevel couldn't find where the brackets end. Check whether all opened brackets are closed.");
-        m.insert(PARSE_ERR_UNK_SYMBOL, "Arevel didn't recognize the symbol.");
+        m.insert(PARSE_ERR_UNK_SYMBOL, "Arevel didn't recognize this name. Check the spelling, or define it in another cell.");
+        m.insert(PARSE_ERR_INVALID_NAME, "Names need to start with a letter, and can only contain letters, numbers and underscores.");
         m.insert(RUNTIME_ERR_INVALID_TYPE, "That data type doesn't work with this operation.");
         m.insert(RUNTIME_ERR_TYPE_NAN, "This operation doesn't work with not-a-number (NaN) values.");
         m.insert(RUNTIME_ERR_EXPECTED_NUM, "Arevel expects a number here.");
@@ -86,9 +110,17 @@ lazy_static! {
         m.insert(RUNTIME_ERR_CIRCULAR_DEP, "There's a circular reference between these cells.");
         m.insert(RUNTIME_ERR_EXPECTED_STR, "Arevel expects some text value here.");
         m.insert(RUNTIME_ERR_DIV_Z, "Dividing by zero is undefined. Make sure the denominator is not a zero before dividing.");
+        m.insert(RUNTIME_ERR_NUM_OVERFLOW, "This number is too large to calculate exactly.");
+        m.insert(RUNTIME_ERR_INVALID_DATE, "This date doesn't exist on the calendar. Check the month and day.");
+        m.insert(RUNTIME_ERR_INDEX_BOUNDS, "This position is outside the list. Positions start at 0, and negative positions count from the end.");
+        m.insert(RUNTIME_ERR_EXPECTED_LIST, "Arevel expects a list here.");
+        m.insert(RUNTIME_ERR_UNK_FIELD, "This field doesn't exist here. Check the spelling of the name after the dot.");
+        m.insert(RUNTIME_ERR_MISSING_FIELD, "A required field is missing. Give a value for every field that doesn't have a default.");
+        m.insert(RUNTIME_ERR_EXPECTED_CLASS, "Arevel expects a record type here, like Customer or Order.");
+        m.insert(RUNTIME_ERR_SYMBOL_OVERFLOW, "This document has run out of space for new values. Try splitting it into smaller documents.");
 
         // TODO
-        m.insert(RUNTIME_ERR_FN_UNK, "Unknown function");
+        m.insert(RUNTIME_ERR_FN_UNK, "Arevel didn't recognize this function. Check the spelling of the name.");
         m.insert(RUNTIME_ERR_FN_ARITY, "Unexpected number of parameters.");
         m.insert(RUNTIME_ERR_FN_EXPECTED, "Arevel expect a valid function here.");
 
@@ -103,6 +135,17 @@ lazy_static! {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_error_stages() {
+        // Runtime codes must not set the parse or interpreter bits
+        for code in ERR_MSG_MAP.keys() {
+            let stage_bits = (code >> 32) & 0x0FF0;
+            if (code >> 32) & 0xF00F != 0 {
+                assert_eq!(stage_bits, 0, "{:X}", code);
+            }
+        }
+    }
+
     #[test]
     fn test_symbol_order() {
         // The RESERVED_SYMBOLS list is meant to be ordered by the symbol ID
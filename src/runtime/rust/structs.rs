@@ -1,31 +1,162 @@
 use crate::environment::Environment;
 use crate::functions::NativeFn;
+use crate::decimal::Decimal;
+use crate::temporal::{Date, DateTime, Duration};
 use crate::utils::{create_string_pointer, create_pointer_symbol, truncate_symbol};
-use fnv::FnvHashMap;
 
 use crate::format::*;
+use crate::constants::{AV_CLASS_OBJECT, PARSE_ERR_USED_NAME, PARSE_ERR_INVALID_NAME, RUNTIME_ERR_CIRCULAR_DEP, RUNTIME_ERR_UNK_VAL, RUNTIME_ERR_FN_UNK};
+use crate::suggest::format_suggestions;
 
 
+// Returned to hosts by __av_typeof, so the values are fixed. Only append new types.
 #[derive(Debug,PartialEq)]
 pub enum ValueType {
-    NumericType,
-    StringType,
-	ObjectType,
-    SymbolType,
-    HashMapType
+    NumericType = 0,
+    StringType = 1,
+	ObjectType = 2,
+    SymbolType = 3,     // Keywords and other inline symbols
+    HashMapType = 4,
+    DecimalType = 5,
+    BoolType = 6,
+    NoneType = 7,
+    ErrorType = 8,
+    ListType = 9,
+    FunctionType = 10,
+    DateType = 11,
+    DateTimeType = 12,
+    DurationType = 13
+}
+
+impl ValueType {
+    // User facing name, as returned by type(x)
+    pub fn name(&self) -> &'static str {
+        match self {
+            ValueType::NumericType => "number",
+            ValueType::DecimalType => "decimal",
+            ValueType::StringType => "text",
+            ValueType::BoolType => "bool",
+            ValueType::NoneType => "none",
+            ValueType::ErrorType => "error",
+            ValueType::ListType => "list",
+            ValueType::HashMapType => "map",
+            ValueType::ObjectType => "object",
+            ValueType::FunctionType => "function",
+            ValueType::SymbolType => "keyword",
+            ValueType::DateType => "date",
+            ValueType::DateTimeType => "datetime",
+            ValueType::DurationType => "duration"
+        }
+    }
+}
+
+// Why a name can't be used. See validate_name
+#[derive(Debug,PartialEq,Clone,Copy)]
+pub enum NameError {
+    Empty,
+    LeadingDigit,
+    InvalidCharacter(char),
+    ReservedWord,
+    BuiltinName,
+    AlreadyUsed
+}
+
+impl NameError {
+    pub fn error_code(&self) -> u64 {
+        match self {
+            NameError::ReservedWord | NameError::BuiltinName | NameError::AlreadyUsed => PARSE_ERR_USED_NAME,
+            _ => PARSE_ERR_INVALID_NAME
+        }
+    }
+
+    // Shown next to the name in the editor
+    pub fn message(&self) -> &'static str {
+        match self {
+            NameError::Empty => "Names can't be blank.",
+            NameError::LeadingDigit => "Names can't start with a number. Try adding a letter before it.",
+            NameError::InvalidCharacter(_) => "Names can only contain letters, numbers and underscores.",
+            NameError::ReservedWord => "This word has a special meaning in Arevel, so it can't be used as a name.",
+            NameError::BuiltinName => "There's a built-in function with this name. Try a different name.",
+            NameError::AlreadyUsed => "This name is already used. Names are case-insensitive."
+        }
+    }
+}
+
+// A name that isn't defined, with similar names that are. See Environment::unknown_symbol
+#[derive(Debug,PartialEq,Clone)]
+pub struct UnknownName {
+    // PARSE_ERR_UNK_SYMBOL or RUNTIME_ERR_FN_UNK
+    pub code: u64,
+    pub name: String,
+    // Best match first
+    pub suggestions: Vec<String>
+}
+
+impl UnknownName {
+    pub fn error_code(&self) -> u64 {
+        return self.code;
+    }
+
+    // i.e. Arevel didn't recognize "Totl". Did you mean "Total"?
+    pub fn message(&self) -> String {
+        let kind = if self.code == RUNTIME_ERR_FN_UNK { "function " } else { "" };
+        let mut message = format!("Arevel didn't recognize the {}name \"{}\".", kind, self.name.trim());
+        if !self.suggestions.is_empty() {
+            message.push_str(&format!(" Did you mean {}?", format_suggestions(&self.suggestions)));
+        }
+        return message;
+    }
+}
+
+// Pointers that lead back to themselves. See Environment::try_resolve
+#[derive(Debug,PartialEq,Clone)]
+pub struct CircularReference {
+    // Each symbol points to the next, and the last points back to the first.
+    pub symbols: Vec<u64>,
+    // Matches symbols. None for unnamed values.
+    pub names: Vec<Option<String>>
+}
+
+impl CircularReference {
+    pub fn error_code(&self) -> u64 {
+        return RUNTIME_ERR_CIRCULAR_DEP;
+    }
+}
+
+#[derive(Debug,PartialEq,Clone)]
+pub enum ResolveError {
+    // The symbol, or one it points to, has no value
+    Undefined(u64),
+    Circular(CircularReference)
+}
+
+impl ResolveError {
+    pub fn error_code(&self) -> u64 {
+        match self {
+            ResolveError::Undefined(_) => RUNTIME_ERR_UNK_VAL,
+            ResolveError::Circular(cycle) => cycle.error_code()
+        }
+    }
 }
 
 #[derive(PartialEq,Clone)]
 pub enum Atom {
     NumericValue(f64),
+    DecimalValue(Decimal),
     StringValue(String),
+    DateValue(Date),
+    DateTimeValue(DateTime),
+    DurationValue(Duration),
     SymbolValue(u64),
+    // List elements are stored as raw values, same as AvObject.av_values.
+    ListValue(Vec<u64>),
     ObjectValue(AvObject),
-    HashMapValue(FnvHashMap<u64, Atom>),
+    // Raw keys and values in a flat hash table. See hashtable.rs and AvObject::new_table
+    HashMapValue(AvObject),
     FunctionValue(NativeFn)
 }
 
-#[derive(Debug)]
+#[derive(Debug,Clone)]
 pub struct Identifier {
     pub symbol: u64,
     pub name: Option<String>,
@@ -42,6 +173,9 @@ pub struct Keyword {
     pub symbol: u64, 
     pub name: &'static str,
     pub precedence: Option<u8>,
+    // Control flow operators. Called with the evaluated left operand before the right one.
+    // Some(result) skips evaluating the right operand. See apply_lazy.
+    pub short_circuit: Option<fn(&Environment, u64) -> Option<u64>>,
     pub operation: Option<extern "C" fn(&mut Environment, u64, u64) -> u64>
 }
 
@@ -77,11 +211,14 @@ pub struct AvObject {
 
 impl AvObject {
     pub fn new() -> AvObject {
-        // TODO: Should take in class and id 
         // Allocate an empty object
+        return AvObject::new_instance(0, AV_CLASS_OBJECT);
+    }
+
+    pub fn new_instance(id: u64, av_class: u64) -> AvObject {
         return AvObject {
-            id: 0,
-            av_class: 0, // TODO
+            id: id,
+            av_class: av_class,
             av_values: None,
         };
     }
@@ -0,0 +1,110 @@
+// "Did you mean" suggestions for unknown names.
+// Candidates are ranked by edit distance ignoring case, then by exact edit distance,
+// so names typed with the same casing rank first. See Environment::unknown_symbol
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::min;
+
+
+// Edits (insert, delete, substitute or swap adjacent chars) to turn a into b.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // Rows for i - 2, i - 1 and i
+    let mut prev2: Vec<usize> = vec![0; b.len() + 1];
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut current: Vec<usize> = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            current[j] = min(min(prev[j] + 1, current[j - 1] + 1), prev[j - 1] + cost);
+            // Transposition. i.e. "Totla" -> "Total"
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = min(current[j], prev2[j - 2] + 1);
+            }
+        }
+        core::mem::swap(&mut prev2, &mut prev);
+        core::mem::swap(&mut prev, &mut current);
+    }
+    return prev[b.len()];
+}
+
+// Edits allowed before a name is too different to be a typo
+fn max_distance(name: &str) -> usize {
+    let len = name.chars().count();
+    return len.div_ceil(3).max(1);
+}
+
+// Up to limit candidates close enough to the name, best first.
+// Candidates that only differ in case from an earlier one are skipped.
+pub fn rank_suggestions<'a, I: Iterator<Item = &'a str>>(name: &str, candidates: I, limit: usize) -> Vec<String> {
+    let name = name.trim();
+    let uname = name.to_uppercase();
+    let threshold = max_distance(name);
+
+    let mut seen: Vec<String> = Vec::new();
+    // (case-insensitive distance, exact distance, candidate)
+    let mut ranked: Vec<(usize, usize, &str)> = Vec::new();
+    for candidate in candidates {
+        let ucandidate = candidate.to_uppercase();
+        if seen.contains(&ucandidate) {
+            continue;
+        }
+        let distance = edit_distance(&uname, &ucandidate);
+        if distance <= threshold {
+            ranked.push((distance, edit_distance(name, candidate), candidate));
+        }
+        seen.push(ucandidate);
+    }
+    ranked.sort();
+    return ranked.into_iter()
+        .take(limit)
+        .map(|(_, _, candidate)| candidate.to_string())
+        .collect();
+}
+
+// "a", "a" or "b", "a", "b" or "c"
+pub fn format_suggestions(suggestions: &[String]) -> String {
+    let quoted: Vec<String> = suggestions.iter().map(|name| format!("\"{}\"", name)).collect();
+    match quoted.split_last() {
+        None => String::new(),
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} or {}", rest.join(", "), last)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("total", "total"), 0);
+        assert_eq!(edit_distance("totl", "total"), 1);
+        assert_eq!(edit_distance("totla", "total"), 1);
+        assert_eq!(edit_distance("price", "prize"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("café", "cafe"), 1);
+    }
+
+    #[test]
+    fn test_rank_suggestions() {
+        let candidates = vec!["Totals", "TOTAL", "tota", "Total", "tax", "Subtotal"];
+        assert_eq!(rank_suggestions("totl", candidates.iter().cloned(), 3),
+            vec!["tota".to_string(), "TOTAL".to_string(), "Totals".to_string()]);
+        assert_eq!(rank_suggestions("xyz", candidates.iter().cloned(), 3), Vec::<String>::new());
+        assert_eq!(rank_suggestions("tx", candidates.iter().cloned(), 1), vec!["tax".to_string()]);
+    }
+
+    #[test]
+    fn test_format_suggestions() {
+        assert_eq!(format_suggestions(&[]), "");
+        assert_eq!(format_suggestions(&["a".to_string()]), "\"a\"");
+        assert_eq!(format_suggestions(&["a".to_string(), "b".to_string(), "c".to_string()]), "\"a\", \"b\" or \"c\"");
+    }
+}
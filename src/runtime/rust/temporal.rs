@@ -0,0 +1,302 @@
+use core::fmt;
+use alloc::string::String;
+
+
+pub const MS_PER_SECOND: i64 = 1000;
+pub const MS_PER_MINUTE: i64 = 60 * MS_PER_SECOND;
+pub const MS_PER_HOUR: i64 = 60 * MS_PER_MINUTE;
+pub const MS_PER_DAY: i64 = 24 * MS_PER_HOUR;
+
+// Dates are limited to ±100,000,000 days from 1970-01-01, the same range as JavaScript's Date.
+// Every date in range can be converted to a DateTime without overflowing.
+pub const MAX_DATE_DAYS: i64 = 100_000_000;
+// Years outside this can't be in range. Checked before the day count is calculated.
+const MAX_YEAR: i64 = 300_000;
+
+// Source of the current time in milliseconds since the unix epoch (UTC).
+// Injectable through Environment.clock so calculations can be replayed and tested.
+pub type Clock = fn() -> i64;
+
+#[cfg(not(target_os = "unknown"))]
+pub fn system_clock() -> i64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    return match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => elapsed.as_millis() as i64,
+        Err(before_epoch) => -(before_epoch.duration().as_millis() as i64)
+    };
+}
+
+// WASM has no system clock. The host is expected to inject one.
+#[cfg(target_os = "unknown")]
+pub fn system_clock() -> i64 {
+    return 0;
+}
+
+
+// All temporal values are UTC. No timezone database is needed.
+// Calendar dates. Days since 1970-01-01 in the proleptic gregorian calendar.
+#[derive(PartialEq,Eq,PartialOrd,Ord,Clone,Copy,Debug)]
+pub struct Date {
+    pub days: i64
+}
+
+// A point in time. Milliseconds since 1970-01-01T00:00:00Z
+#[derive(PartialEq,Eq,PartialOrd,Ord,Clone,Copy,Debug)]
+pub struct DateTime {
+    pub millis: i64
+}
+
+// Elapsed time in milliseconds. Can be negative.
+#[derive(PartialEq,Eq,PartialOrd,Ord,Clone,Copy,Debug)]
+pub struct Duration {
+    pub millis: i64
+}
+
+
+pub fn is_leap_year(year: i64) -> bool {
+    return (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+}
+
+pub fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 => if is_leap_year(year) { 29 } else { 28 },
+        _ => 0
+    }
+}
+
+// Howard Hinnant's days_from_civil. Exact for all representable years.
+// http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = (if y >= 0 { y } else { y - 399 }) / 400;
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    return era * 146097 + doe - 719468;
+}
+
+// Widened so dates outside the supported range can still be displayed
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days as i128 + 719468;
+    let era = (if z >= 0 { z } else { z - 146096 }) / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + (if month <= 2 { 1 } else { 0 });
+    return (year as i64, month, day);
+}
+
+
+impl Date {
+    // Returns None for dates that don't exist on the calendar. i.e. 2021-02-29
+    // Also None outside the supported range. See MAX_DATE_DAYS
+    pub fn from_ymd(year: i64, month: u32, day: u32) -> Option<Date> {
+        if !(-MAX_YEAR..=MAX_YEAR).contains(&year) || !(1..=12).contains(&month) {
+            return None;
+        }
+        if day < 1 || day > days_in_month(year, month) {
+            return None;
+        }
+        return Date::from_days(days_from_civil(year, month, day));
+    }
+
+    pub fn from_days(days: i64) -> Option<Date> {
+        if !(-MAX_DATE_DAYS..=MAX_DATE_DAYS).contains(&days) {
+            return None;
+        }
+        return Some(Date { days: days });
+    }
+
+    pub fn today(clock: Clock) -> Date {
+        return DateTime { millis: clock() }.date();
+    }
+
+    pub fn ymd(&self) -> (i64, u32, u32) {
+        return civil_from_days(self.days);
+    }
+
+    // ISO weekday. Monday = 1 ... Sunday = 7
+    pub fn weekday(&self) -> u32 {
+        // 1970-01-01 was a Thursday
+        return ((self.days + 3).rem_euclid(7) + 1) as u32;
+    }
+
+    // None if the result is outside the supported range
+    pub fn add_days(&self, days: i64) -> Option<Date> {
+        return Date::from_days(self.days.checked_add(days)?);
+    }
+
+    pub fn days_since(&self, other: &Date) -> Option<i64> {
+        return self.days.checked_sub(other.days);
+    }
+
+    // Midnight UTC. Only None for dates built outside the supported range
+    pub fn to_datetime(&self) -> Option<DateTime> {
+        return Some(DateTime { millis: self.days.checked_mul(MS_PER_DAY)? });
+    }
+}
+
+impl DateTime {
+    pub fn date(&self) -> Date {
+        return Date { days: self.millis.div_euclid(MS_PER_DAY) };
+    }
+
+    pub fn add(&self, duration: &Duration) -> Option<DateTime> {
+        return Some(DateTime { millis: self.millis.checked_add(duration.millis)? });
+    }
+
+    pub fn since(&self, other: &DateTime) -> Option<Duration> {
+        return Some(Duration { millis: self.millis.checked_sub(other.millis)? });
+    }
+}
+
+impl Duration {
+    pub fn from_days(days: i64) -> Option<Duration> {
+        return Some(Duration { millis: days.checked_mul(MS_PER_DAY)? });
+    }
+
+    // None for the most negative duration, which has no positive counterpart
+    pub fn checked_neg(&self) -> Option<Duration> {
+        return Some(Duration { millis: self.millis.checked_neg()? });
+    }
+
+    pub fn is_whole_days(&self) -> bool {
+        return self.millis % MS_PER_DAY == 0;
+    }
+
+    pub fn days(&self) -> i64 {
+        return self.millis / MS_PER_DAY;
+    }
+}
+
+
+// ISO 8601 formats. 2020-03-05, 2020-03-05T10:20:30Z, P1DT2H
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = self.ymd();
+        if !(0..=9999).contains(&year) {
+            // Expanded representation for years outside 4 digits
+            write!(f, "{:+05}-{:02}-{:02}", year, month, day)
+        } else {
+            write!(f, "{:04}-{:02}-{:02}", year, month, day)
+        }
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = self.millis.rem_euclid(MS_PER_DAY);
+        let hours = time / MS_PER_HOUR;
+        let minutes = (time % MS_PER_HOUR) / MS_PER_MINUTE;
+        let seconds = (time % MS_PER_MINUTE) / MS_PER_SECOND;
+        let millis = time % MS_PER_SECOND;
+        write!(f, "{}T{:02}:{:02}:{:02}", self.date(), hours, minutes, seconds)?;
+        if millis != 0 {
+            write!(f, ".{:03}", millis)?;
+        }
+        write!(f, "Z")
+    }
+}
+
+impl fmt::Display for Duration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut output = String::new();
+        if self.millis < 0 {
+            output.push('-');
+        }
+        // Unsigned, since i64::MIN has no positive i64
+        let total = self.millis.unsigned_abs();
+        let days = total / MS_PER_DAY as u64;
+        let hours = (total % MS_PER_DAY as u64) / MS_PER_HOUR as u64;
+        let minutes = (total % MS_PER_HOUR as u64) / MS_PER_MINUTE as u64;
+        let seconds = (total % MS_PER_MINUTE as u64) / MS_PER_SECOND as u64;
+        let millis = total % MS_PER_SECOND as u64;
+
+        output.push('P');
+        if days != 0 {
+            output.push_str(&format!("{}D", days));
+        }
+        if hours != 0 || minutes != 0 || seconds != 0 || millis != 0 || days == 0 {
+            output.push('T');
+            if hours != 0 {
+                output.push_str(&format!("{}H", hours));
+            }
+            if minutes != 0 {
+                output.push_str(&format!("{}M", minutes));
+            }
+            if millis != 0 {
+                output.push_str(&format!("{}.{:03}S", seconds, millis));
+            } else if seconds != 0 || (hours == 0 && minutes == 0) {
+                output.push_str(&format!("{}S", seconds));
+            }
+        }
+        write!(f, "{}", output)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed_clock() -> i64 {
+        // 2020-02-29T12:00:00Z
+        return 1582977600000;
+    }
+
+    #[test]
+    fn test_civil_roundtrip() {
+        assert_eq!(Date::from_ymd(1970, 1, 1).unwrap().days, 0);
+        assert_eq!(Date::from_ymd(2000, 3, 1).unwrap().ymd(), (2000, 3, 1));
+        assert_eq!(Date::from_ymd(1969, 12, 31).unwrap().days, -1);
+        assert_eq!(Date::from_ymd(2021, 2, 29), None);
+        assert_eq!(Date::from_ymd(2021, 13, 1), None);
+    }
+
+    #[test]
+    fn test_date_range() {
+        assert!(Date::from_ymd(1_000_000_000_000_000_000, 1, 1).is_none());
+        assert!(Date::from_ymd(-300_001, 1, 1).is_none());
+        let max = Date::from_days(MAX_DATE_DAYS).unwrap();
+        assert!(Date::from_days(MAX_DATE_DAYS + 1).is_none());
+        assert!(max.add_days(1).is_none());
+        assert!(max.add_days(i64::MAX).is_none());
+        assert_eq!(max.to_datetime().unwrap().millis, MAX_DATE_DAYS * MS_PER_DAY);
+        assert_eq!(Date { days: i64::MAX }.to_datetime(), None);
+        assert_eq!(Date { days: i64::MIN }.days_since(&max), None);
+    }
+
+    #[test]
+    fn test_weekday() {
+        // Thursday
+        assert_eq!(Date::from_ymd(1970, 1, 1).unwrap().weekday(), 4);
+        // Sunday
+        assert_eq!(Date::from_ymd(2020, 3, 1).unwrap().weekday(), 7);
+        assert_eq!(Date::from_ymd(1969, 12, 29).unwrap().weekday(), 1);
+    }
+
+    #[test]
+    fn test_today_clock() {
+        assert_eq!(Date::today(fixed_clock), Date::from_ymd(2020, 2, 29).unwrap());
+    }
+
+    #[test]
+    fn test_iso_format() {
+        let date = Date::from_ymd(2020, 3, 5).unwrap();
+        assert_eq!(date.to_string(), "2020-03-05");
+        let datetime = DateTime { millis: fixed_clock() + 1500 };
+        assert_eq!(datetime.to_string(), "2020-02-29T12:00:01.500Z");
+        assert_eq!(Duration { millis: MS_PER_DAY * 3 + MS_PER_HOUR * 4 }.to_string(), "P3DT4H");
+        assert_eq!(Duration { millis: -MS_PER_DAY }.to_string(), "-P1D");
+        assert_eq!(Duration { millis: 0 }.to_string(), "PT0S");
+        assert_eq!(Duration { millis: i64::MIN }.to_string(), "-P106751991167DT7H12M55.808S");
+        assert_eq!(Duration { millis: i64::MIN }.checked_neg(), None);
+        assert_eq!(Duration { millis: 90 * MS_PER_SECOND }.to_string(), "PT1M30S");
+    }
+}
@@ -1,5 +1,6 @@
 use crate::constants::*;
 use crate::structs::*;
+use crate::environment::Environment;
 
 #[no_mangle]
 #[inline(always)]
@@ -10,6 +11,14 @@ pub extern "C" fn is_nan(f: f64) -> bool {
 }
 
 
+// NaN from a floating point operation (i.e. sqrt(-1)), as opposed to a NaN-boxed value.
+// Headers at or below SIGNALING_NAN are never used for boxing.
+#[no_mangle]
+#[inline(always)]
+pub extern "C" fn is_float_nan(value: u64) -> bool {
+	return is_nan(f64::from_bits(value)) && (value & VALHEAD_MASK) <= SIGNALING_NAN;
+}
+
 #[no_mangle]
 #[inline(always)]
 pub extern "C" fn is_number(value: u64) -> bool {
@@ -68,20 +77,52 @@ pub extern "C" fn is_error(value: u64) -> bool {
 }
 
 
+// The error without its origin. Use this to compare errors or look up messages.
+#[inline(always)]
+pub fn error_code(value: u64) -> u64 {
+	return value & ERR_CODE_MASK;
+}
+
+// Pointer to the cell where the error first occurred, if known.
+pub fn error_origin(value: u64) -> Option<u64> {
+	let origin = value & ERR_ORIGIN_MASK;
+	if !is_error(value) || origin == 0 {
+		return None;
+	}
+	return Some(origin | VALUE_T_PTR_OBJ);
+}
+
+// Attach the failing cell to an error. Errors that already have an origin keep it.
+pub fn with_error_origin(value: u64, symbol: u64) -> u64 {
+	let payload = symbol & PAYLOAD_MASK;
+	if !is_error(value) || error_origin(value).is_some() || payload > ERR_ORIGIN_MASK {
+		return value;
+	}
+	return value | payload;
+}
+
+
 #[no_mangle]
 // Use this only returning type info.
 // Use the dedicated is_* function to check type more efficiently.
+// Classifies by the NaN-box header alone. Pointers are reported as ObjectType. See __av_type.
 pub extern "C" fn __av_typeof(value: u64) -> ValueType {
 	if (value & SIGNALING_NAN) == SIGNALING_NAN {
 		if (value & VALHEAD_OBJTYPE_MASK) != VALHEAD_OBJTYPE_MASK {
 			return ValueType::StringType;
+		} else if value == SYMBOL_TRUE.symbol || value == SYMBOL_FALSE.symbol {
+			return ValueType::BoolType;
+		} else if value == SYMBOL_NONE.symbol {
+			return ValueType::NoneType;
+		} else if value == SYMBOL_EMPTY_ARR {
+			return ValueType::ListType;
 		} else {
 			let valhead = value & VALHEAD_MASK;
 			match valhead {
 				VALUE_T_SYM_OBJ => return ValueType::SymbolType,
 				VALUE_T_PTR_OBJ => return ValueType::ObjectType, 
 				VALUE_F_SYM_OBJ => return ValueType::SymbolType,
-				VALUE_F_PTR_OBJ => return ValueType::ObjectType,
+				VALUE_F_PTR_OBJ => return ValueType::ErrorType,
 				_ => return ValueType::NumericType  // Treat other values as NaN
 			}
 		}
@@ -90,13 +131,86 @@ pub extern "C" fn __av_typeof(value: u64) -> ValueType {
 	}
 }
 
+pub fn atom_type(atom: &Atom) -> ValueType {
+	match atom {
+		Atom::NumericValue(_) => ValueType::NumericType,
+		Atom::DecimalValue(_) => ValueType::DecimalType,
+		Atom::StringValue(_) => ValueType::StringType,
+		Atom::DateValue(_) => ValueType::DateType,
+		Atom::DateTimeValue(_) => ValueType::DateTimeType,
+		Atom::DurationValue(_) => ValueType::DurationType,
+		Atom::SymbolValue(symbol) => __av_typeof(*symbol),
+		Atom::ListValue(_) => ValueType::ListType,
+		Atom::ObjectValue(_) => ValueType::ObjectType,
+		Atom::HashMapValue(_) => ValueType::HashMapType,
+		Atom::FunctionValue(_) => ValueType::FunctionType
+	}
+}
+
+// Full type of a value, following pointers to what they reference.
+pub fn __av_type(env: &Environment, value: u64) -> ValueType {
+	let header_type = __av_typeof(value);
+	if header_type != ValueType::ObjectType {
+		return header_type;
+	}
+	if let Some(ident) = env.deep_resolve(value) {
+		if let Some(atom) = &ident.value {
+			return atom_type(atom);
+		}
+	}
+	// Builtins are functions even when the prelude isn't loaded
+	#[cfg(not(target_os = "unknown"))]
+	{
+		for module in crate::runtime::BUILTIN_MODULES.iter() {
+			if module.symbol == value {
+				return atom_type(&module.value);
+			}
+		}
+	}
+	return ValueType::ObjectType;
+}
+
 
+// Header-only truthiness. Heap values are always true here. See __av_truthy.
 #[no_mangle]
 pub extern "C" fn __av_as_bool(a: u64) -> bool {
-	// TODO: 'bool' fields for objects
 	return is_truthy(a);
 }
 
+// Truthiness used by not, and, or and conditionals.
+// False, None, errors, 0, NaN, empty text, empty lists and empty maps are false.
+// Objects are true, unless they define a bool field, which then decides.
+pub fn __av_truthy(env: &Environment, value: u64) -> bool {
+	if !is_pointer(value) || is_error(value) {
+		return is_truthy(value);
+	}
+	match env.deep_resolve(value).and_then(|ident| ident.value.as_ref()) {
+		Some(Atom::NumericValue(num)) => *num > 0.0 || *num < 0.0,
+		Some(Atom::DecimalValue(dec)) => !dec.is_zero(),
+		Some(Atom::StringValue(text)) => !text.is_empty(),
+		Some(Atom::ListValue(items)) => !items.is_empty(),
+		Some(Atom::HashMapValue(map)) => map.table_len() > 0,
+		Some(Atom::SymbolValue(symbol)) => is_truthy(*symbol),
+		Some(Atom::ObjectValue(obj)) => {
+			if obj.is_table() {
+				if let Some(flag) = obj.table_get(AV_PROP_BOOL.symbol) {
+					// A flag that is itself an object isn't followed, so an object
+					// can't refer back to itself
+					if let Some(Atom::ObjectValue(_)) = env.deep_resolve(flag).and_then(|ident| ident.value.as_ref()) {
+						return true;
+					}
+					return __av_truthy(env, flag);
+				}
+				// Plain objects are maps. Record instances are always true.
+				return obj.av_class != AV_CLASS_OBJECT || obj.table_len() > 0;
+			}
+			true
+		},
+		Some(_) => true,
+		None => is_truthy(value)
+	}
+}
+
 #[inline(always)]
 pub extern "C" fn __repr_bool(a: bool) -> u64 {
 	if a {
@@ -130,6 +244,46 @@ mod tests {
 	}
 
 
+	#[test]
+	fn test_typeof() {
+		assert_eq!(__av_typeof(f64::to_bits(1.5)), ValueType::NumericType);
+		assert_eq!(__av_typeof(SYMBOL_TRUE.symbol), ValueType::BoolType);
+		assert_eq!(__av_typeof(SYMBOL_FALSE.symbol), ValueType::BoolType);
+		assert_eq!(__av_typeof(SYMBOL_NONE.symbol), ValueType::NoneType);
+		assert_eq!(__av_typeof(RUNTIME_ERR_DIV_Z), ValueType::ErrorType);
+		assert_eq!(__av_typeof(SYMBOL_EMPTY_ARR), ValueType::ListType);
+		assert_eq!(__av_typeof(SYMBOL_PLUS.symbol), ValueType::SymbolType);
+		assert_eq!(__av_typeof(SYMBOL_EMPTY_STR), ValueType::StringType);
+	}
+
+	#[test]
+	fn test_type_ids_stable() {
+		// Hosts compare these ids, so existing ones can't change
+		assert_eq!(ValueType::NumericType as u32, 0);
+		assert_eq!(ValueType::StringType as u32, 1);
+		assert_eq!(ValueType::ObjectType as u32, 2);
+		assert_eq!(ValueType::SymbolType as u32, 3);
+		assert_eq!(ValueType::HashMapType as u32, 4);
+	}
+
+	#[test]
+	fn test_type_resolved() {
+		let mut env = Environment::new(APP_SYMBOL_START);
+		let text = env.init_value(Atom::StringValue("Hello".to_string()));
+		let list = env.init_value(Atom::ListValue(vec![0]));
+		let map = env.init_value(Atom::HashMapValue(AvObject::new_table(0)));
+		let func = env.init_value(AV_FN_ABS.value.clone());
+		let obj = env.init_value(Atom::ObjectValue(AvObject::new()));
+		assert_eq!(__av_type(&env, text), ValueType::StringType);
+		assert_eq!(__av_type(&env, list), ValueType::ListType);
+		assert_eq!(__av_type(&env, map), ValueType::HashMapType);
+		assert_eq!(__av_type(&env, func), ValueType::FunctionType);
+		assert_eq!(__av_type(&env, obj), ValueType::ObjectType);
+		assert_eq!(__av_type(&env, AV_FN_MIN.symbol), ValueType::FunctionType);
+		assert_eq!(__av_type(&env, VALUE_T_PTR_OBJ | (APP_SYMBOL_START + 500)), ValueType::ObjectType);
+		assert_eq!(__av_type(&env, RUNTIME_ERR_DIV_Z), ValueType::ErrorType);
+	}
+
 	#[test]
 	fn test_is_object() {
 		assert_eq!(is_object(VALUE_F_PTR_OBJ), true);
@@ -178,6 +332,19 @@ mod tests {
 	}
 
 
+	#[test]
+	fn test_error_origin() {
+		let cell = APP_SYMBOL_START | VALUE_T_PTR_OBJ;
+		let tagged = with_error_origin(RUNTIME_ERR_DIV_Z, cell);
+		assert_eq!(is_error(tagged), true);
+		assert_eq!(error_code(tagged), RUNTIME_ERR_DIV_Z);
+		assert_eq!(error_origin(tagged), Some(cell));
+		assert_eq!(error_origin(RUNTIME_ERR_DIV_Z), None);
+		// The first failure is kept
+		assert_eq!(with_error_origin(tagged, cell + 1), tagged);
+		assert_eq!(with_error_origin(f64::to_bits(1.0), cell), f64::to_bits(1.0));
+	}
+
 	#[test]
 	fn test_is_error() {
 		assert_eq!(is_error(VALUE_F_PTR_OBJ), true);
@@ -1,4 +1,4 @@
-use crate::constants::{VALUE_T_SYM_OBJ, VALUE_T_PTR_OBJ, VALUE_T_PTR_STR, LOW32_MASK};
+use crate::constants::{VALUE_T_SYM_OBJ, VALUE_T_PTR_OBJ, VALUE_T_PTR_STR, VALUE_T_SYM_STR, VALHEAD_MASK, PAYLOAD_MASK, LOW32_MASK, SYMBOL_EMPTY_STR};
 
 // Unwrap pointer
 #[inline(always)]
@@ -25,3 +25,37 @@ pub fn create_string_pointer(raw: u64) -> u64 {
     return raw | VALUE_T_PTR_STR;
 }
 
+
+
+// Small strings up to 6 bytes of UTF-8 are stored inline in the payload.
+// First byte in the lowest bits. Unused bytes are zero, so these can't contain NUL.
+pub fn create_small_string(text: &str) -> Option<u64> {
+    let bytes = text.as_bytes();
+    if bytes.is_empty() {
+        return Some(SYMBOL_EMPTY_STR);
+    }
+    if bytes.len() > 6 || bytes.contains(&0) {
+        return None;
+    }
+    let mut payload: u64 = 0;
+    for (i, byte) in bytes.iter().enumerate() {
+        payload |= (*byte as u64) << (i * 8);
+    }
+    return Some(payload | VALUE_T_SYM_STR);
+}
+
+pub fn decode_small_string(value: u64) -> Option<String> {
+    if value == SYMBOL_EMPTY_STR {
+        return Some(String::new());
+    }
+    if value & VALHEAD_MASK != VALUE_T_SYM_STR {
+        return None;
+    }
+    let mut payload = value & PAYLOAD_MASK;
+    let mut bytes = Vec::with_capacity(6);
+    while payload != 0 {
+        bytes.push((payload & 0xFF) as u8);
+        payload >>= 8;
+    }
+    return String::from_utf8(bytes).ok();
+}
\ No newline at end of file